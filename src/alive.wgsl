@@ -0,0 +1,19 @@
+// Which boid slots are in use, imported by compute.wgsl, compute3d.wgsl and grid.wgsl.
+// population.wgsl writes the same buffer
+
+// PopulationState struct that matches StateHeader in population.rs, read-only here
+struct PopulationState {
+    // slots in use, alive or dead. Nothing past them is looked at
+    slots: u32,
+    live: u32,
+    free_count: u32,
+    fill: u32,
+    alive: array<u32>,
+}
+
+@group(0) @binding(12)
+var<storage, read> population: PopulationState;
+
+fn is_alive(i: u32) -> bool {
+    return population.alive[i] != 0u;
+}
@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+
+use crate::shaders;
+
+// the arena buffer has room for a polygon with this many corners
+pub const MAX_ARENA_POINTS: usize = 256;
+// segments the circle and the ellipse are drawn with, must match CURVE_SEGMENTS in arena_lines.wgsl
+const ARENA_CURVE_SEGMENTS: u32 = 128;
+
+// The shape of the 2D world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ArenaShape {
+    // the [-1, 1] square filling the window, wrapping around its edges
+    #[default]
+    Square,
+    Circle,
+    Ellipse,
+    // any closed polygon, drawn with the mouse or loaded from a scenario
+    Polygon,
+}
+
+impl ArenaShape {
+    pub const ALL: [ArenaShape; 4] = [ArenaShape::Square, ArenaShape::Circle, ArenaShape::Ellipse, ArenaShape::Polygon];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArenaShape::Square => "square",
+            ArenaShape::Circle => "circle",
+            ArenaShape::Ellipse => "ellipse",
+            ArenaShape::Polygon => "polygon",
+        }
+    }
+
+    // must match the ARENA_* constants in arena_types.wgsl
+    fn id(self) -> u32 {
+        self as u32
+    }
+}
+
+// The walls the boids stay within, as edited in the panel and saved in scenario files.
+// Boids turn away from a wall once they're within `margin` of it, harder the closer they get
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArenaSettings {
+    pub shape: ArenaShape,
+    pub radius: f32,
+    // half widths of the ellipse along x and y
+    pub radii: [f32; 2],
+    // corners of the polygon, the last one leads back to the first
+    pub points: Vec<[f32; 2]>,
+    pub margin: f32,
+    // acceleration away from a wall at the wall itself
+    pub strength: f32,
+}
+
+impl Default for ArenaSettings {
+    fn default() -> Self {
+        Self {
+            shape: ArenaShape::Square,
+            radius: 0.9,
+            radii: [0.95, 0.6],
+            points: regular_polygon(6, 0.9),
+            margin: 0.1,
+            strength: 20.0,
+        }
+    }
+}
+
+// `sides` corners evenly spaced on a circle of `radius`
+pub fn regular_polygon(sides: u32, radius: f32) -> Vec<[f32; 2]> {
+    (0..sides).map(|k| {
+        let angle = k as f32 / sides as f32 * std::f32::consts::TAU;
+        [radius * angle.cos(), radius * angle.sin()]
+    }).collect()
+}
+
+// Arena struct that matches arena_types.wgsl, the polygon's corners follow it
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuArena {
+    shape: u32,
+    count: u32,
+    margin: f32,
+    strength: f32,
+    radii: [f32; 2],
+}
+
+// The arena of the 2D mode, uploaded for the compute kernel and drawn as lines
+pub struct Arena {
+    pub settings: ArenaSettings,
+    // the mouse draws a new polygon instead of selecting boids
+    pub drawing: bool,
+    pub buffer: wgpu::Buffer,
+    line_pipeline: wgpu::RenderPipeline,
+    line_bind_group: wgpu::BindGroup,
+}
+
+impl Arena {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Arena Buffer"),
+            size: (std::mem::size_of::<GpuArena>() + MAX_ARENA_POINTS * 8) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut line_entry = Self::layout_entry(wgpu::ShaderStages::VERTEX);
+        line_entry.binding = 0;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[line_entry],
+            label: Some("arena lines bind group layout"),
+        });
+        let line_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("arena lines bind group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Arena Lines Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("arena_lines.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Arena Lines Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Arena Lines Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("arena_vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("arena_fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            settings: ArenaSettings::default(),
+            drawing: false,
+            buffer,
+            line_pipeline,
+            line_bind_group,
+        }
+    }
+
+    // the arena buffer binding of the compute kernel's layout
+    pub fn layout_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: 13,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    // start a new polygon at `position`, replacing the current one
+    pub fn begin(&mut self, position: [f32; 2]) {
+        self.settings.shape = ArenaShape::Polygon;
+        self.settings.points = vec![position];
+    }
+
+    // add a corner to the polygon being drawn if it's at least `spacing` from the previous one
+    pub fn extend(&mut self, position: [f32; 2], spacing: f32) {
+        let points = &mut self.settings.points;
+        let Some(&last) = points.last() else {
+            return;
+        };
+        if points.len() < MAX_ARENA_POINTS && (position[0] - last[0]).hypot(position[1] - last[1]) >= spacing {
+            points.push(position);
+        }
+    }
+
+    // finish the polygon, which always closes. One without room inside goes back to the default
+    pub fn end(&mut self) {
+        if self.settings.points.len() < 3 {
+            self.settings.points = ArenaSettings::default().points;
+        }
+    }
+
+    // replace the settings, e.g. with the ones from a scenario, dropping the corners that don't fit in the buffer
+    pub fn set_settings(&mut self, mut settings: ArenaSettings) {
+        settings.points.truncate(MAX_ARENA_POINTS);
+        self.settings = settings;
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue) {
+        let settings = &self.settings;
+        let radii = match settings.shape {
+            ArenaShape::Circle => [settings.radius; 2],
+            _ => settings.radii,
+        };
+        let arena = GpuArena {
+            shape: settings.shape.id(),
+            count: settings.points.len() as u32,
+            margin: settings.margin,
+            strength: settings.strength,
+            radii,
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&arena));
+        if !settings.points.is_empty() {
+            queue.write_buffer(&self.buffer, std::mem::size_of::<GpuArena>() as u64, bytemuck::cast_slice(&settings.points));
+        }
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        let segments = match self.settings.shape {
+            ArenaShape::Square => return,
+            ArenaShape::Circle | ArenaShape::Ellipse => ARENA_CURVE_SEGMENTS,
+            // a single point or a line while it's being drawn
+            ArenaShape::Polygon => self.settings.points.len() as u32,
+        };
+        render_pass.set_pipeline(&self.line_pipeline);
+        render_pass.set_bind_group(0, &self.line_bind_group, &[]);
+        render_pass.draw(0..2 * segments, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::{assert_layout, offsets, wgsl_struct};
+
+    #[test]
+    fn arena_matches_the_shader() {
+        let fields = offsets!(GpuArena { shape, count, margin, strength, radii });
+        assert_layout("arena_types.wgsl", "Arena", fields, None);
+        // the corners go right after the header
+        let (members, _) = wgsl_struct("arena_types.wgsl", "Arena");
+        assert!(members.contains(&("points".to_string(), std::mem::size_of::<GpuArena>())));
+    }
+}
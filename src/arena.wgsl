@@ -0,0 +1,86 @@
+// The walls of the 2D world, imported by compute.wgsl. In the square the boids wrap around the edges.
+// In the other shapes they turn away from a wall once they're within `arena.margin` of it,
+// and a boid that gets through anyway is put back inside
+
+#import boid2d_types.wgsl
+#import arena_types.wgsl
+#import flight.wgsl
+
+@group(0) @binding(13)
+var<storage, read> arena: Arena;
+
+// how far a point is from the nearest wall, negative outside, and the way from that wall into the arena
+struct Wall {
+    dist: f32,
+    inward: vec2<f32>,
+}
+
+// whether the arena has walls, a polygon needs three corners
+fn walled() -> bool {
+    return arena.shape == ARENA_CIRCLE || arena.shape == ARENA_ELLIPSE || (arena.shape == ARENA_POLYGON && arena.count >= 3u);
+}
+
+fn nearest_wall(pos: vec2<f32>) -> Wall {
+    if (arena.shape == ARENA_POLYGON) {
+        return polygon_wall(pos);
+    }
+    // the circle is an ellipse with equal radii. The distance is first order, |p / radii| - 1 over its gradient,
+    // which is exact for a circle and close enough near the wall of an ellipse
+    let radii = max(arena.radii, vec2<f32>(1.0e-3));
+    let q = pos / radii;
+    let g = length(q);
+    let gradient = safe_normalize(vec3<f32>(q / radii, 0.0), vec3<f32>(1.0, 0.0, 0.0)).xy;
+    let slope = max(length(q / (radii * max(g, 1.0e-6))), 1.0e-6);
+    return Wall((1.0 - g) / slope, -gradient);
+}
+
+// the nearest point over every edge, inside by the even-odd rule
+fn polygon_wall(pos: vec2<f32>) -> Wall {
+    var best = 1.0e30;
+    var closest = pos;
+    var inside = false;
+    for (var k = 0u; k < arena.count; k++) {
+        let a = arena.points[k];
+        let b = arena.points[select(k + 1u, 0u, k + 1u == arena.count)];
+        let ab = b - a;
+        let t = clamp(dot(pos - a, ab) / max(dot(ab, ab), 1.0e-12), 0.0, 1.0);
+        let point = a + ab * t;
+        let dist = distance(pos, point);
+        if (dist < best) {
+            best = dist;
+            closest = point;
+        }
+        if ((a.y > pos.y) != (b.y > pos.y) && pos.x < a.x + (pos.y - a.y) * ab.x / ab.y) {
+            inside = !inside;
+        }
+    }
+    let away = safe_normalize(vec3<f32>(pos - closest, 0.0), vec3<f32>(0.0)).xy;
+    return Wall(select(-best, best, inside), select(-away, away, inside));
+}
+
+// acceleration turning a boid at `pos` away from a wall within the margin, growing to `arena.strength` at the wall
+fn wall_steering(pos: vec2<f32>) -> vec2<f32> {
+    if (!walled()) {
+        return vec2<f32>(0.0);
+    }
+    let wall = nearest_wall(pos);
+    if (wall.dist >= arena.margin) {
+        return vec2<f32>(0.0);
+    }
+    let closeness = 1.0 - clamp(wall.dist / max(arena.margin, 1.0e-6), 0.0, 1.0);
+    return wall.inward * arena.strength * closeness;
+}
+
+// `boid` kept in the world after a step: wrapped around the square, or put back just inside a wall it got through,
+// with the part of its velocity heading out turned back in
+fn contain(boid: Boid) -> Boid {
+    if (!walled()) {
+        return Boid(select(boid.pos, -sign(boid.pos), abs(boid.pos) > vec2<f32>(1.0)), boid.vel);
+    }
+    let wall = nearest_wall(boid.pos);
+    if (wall.dist >= 0.0) {
+        return boid;
+    }
+    let outward = min(dot(boid.vel, wall.inward), 0.0);
+    return Boid(boid.pos + wall.inward * (1.0e-4 - wall.dist), boid.vel - wall.inward * outward * 2.0);
+}
@@ -0,0 +1,35 @@
+// The walls of the arena as lines, the square has none to draw
+
+#import arena_types.wgsl
+#import math.wgsl
+
+@group(0) @binding(0)
+var<storage, read> arena: Arena;
+
+// must match ARENA_CURVE_SEGMENTS in arena.rs
+const CURVE_SEGMENTS : u32 = 128u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// two vertices per segment, the last one leading back to the first point
+@vertex
+fn arena_vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let k = index / 2u + index % 2u;
+    var pos: vec2<f32>;
+    if (arena.shape == ARENA_POLYGON) {
+        pos = arena.points[k % arena.count];
+    } else {
+        let angle = f32(k) / f32(CURVE_SEGMENTS) * TAU;
+        pos = arena.radii * vec2<f32>(cos(angle), sin(angle));
+    }
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(pos, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn arena_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.55, 0.85, 1.0, 1.0); // pale blue
+}
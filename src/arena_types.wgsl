@@ -0,0 +1,18 @@
+// Arena struct that matches arena.rs, read by arena.wgsl and arena_lines.wgsl
+
+// must match the ArenaShape ids in arena.rs
+const ARENA_SQUARE : u32 = 0u;
+const ARENA_CIRCLE : u32 = 1u;
+const ARENA_ELLIPSE : u32 = 2u;
+const ARENA_POLYGON : u32 = 3u;
+
+struct Arena {
+    shape: u32,
+    // corners of the polygon
+    count: u32,
+    margin: f32,
+    strength: f32,
+    // of the ellipse along x and y, both the same for a circle
+    radii: vec2<f32>,
+    points: array<vec2<f32>>,
+}
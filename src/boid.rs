@@ -3,8 +3,8 @@ use rand::Rng;
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Boid {
-    pos: [f32; 2],
-    vel: [f32; 2],
+    pub pos: [f32; 2],
+    pub vel: [f32; 2],
 }
 
 pub fn generate_boids(population: u32) -> Vec<Boid> { 
@@ -56,4 +56,121 @@ pub fn triangle_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
             format: wgpu::VertexFormat::Float32x2,
         }],
     }
-}
\ No newline at end of file
+}
+
+// Boid of the 3D mode. vec3 is 16 byte aligned in WGSL storage buffers, hence the padding
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Boid3d {
+    pub pos: [f32; 3],
+    pub _pad0: f32,
+    pub vel: [f32; 3],
+    pub _pad1: f32,
+}
+
+pub fn generate_boids_3d(population: u32) -> Vec<Boid3d> {
+    let mut rng = rand::thread_rng();
+
+    (0..population)
+    .map(|_| {
+        // random direction and position, both uniform on the sphere
+        let direction = |rng: &mut rand::rngs::ThreadRng| {
+            let z: f32 = rng.gen_range(-1.0..1.0);
+            let theta = rng.gen_range(0.0..std::f32::consts::TAU);
+            let r = (1.0 - z * z).sqrt();
+            [r * theta.cos(), r * theta.sin(), z]
+        };
+        let speed = rng.gen_range(0.005..0.015);
+        let vel = direction(&mut rng).map(|v| v * speed);
+        // constraint intial positions to be in a spherical shell
+        let radius = rng.gen_range(0.1..0.7);
+        let pos = direction(&mut rng).map(|p| p * radius);
+
+        Boid3d { pos, _pad0: 0.0, vel, _pad1: 0.0 }
+    }).collect()
+}
+
+// boids of the sphere mode, spread uniformly over the unit sphere and flying along it in random directions
+pub fn generate_boids_sphere(population: u32) -> Vec<Boid3d> {
+    let mut rng = rand::thread_rng();
+
+    (0..population)
+    .map(|_| {
+        let z: f32 = rng.gen_range(-1.0..1.0);
+        let theta = rng.gen_range(0.0..std::f32::consts::TAU);
+        let r = (1.0 - z * z).sqrt();
+        let pos = [r * theta.cos(), r * theta.sin(), z];
+        // east and north at that point span the tangent plane, the poles are never drawn exactly
+        let east = [-theta.sin(), theta.cos(), 0.0];
+        let north = [-z * theta.cos(), -z * theta.sin(), r];
+        let heading = rng.gen_range(0.0..std::f32::consts::TAU);
+        let speed = rng.gen_range(0.005..0.015);
+        let vel = std::array::from_fn(|k| (east[k] * heading.cos() + north[k] * heading.sin()) * speed);
+
+        Boid3d { pos, _pad0: 0.0, vel, _pad1: 0.0 }
+    }).collect()
+}
+
+impl Boid3d {
+    // vel starts after pos and its padding
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = [
+        wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x3 },
+        wgpu::VertexAttribute { offset: 16, shader_location: 1, format: wgpu::VertexFormat::Float32x3 },
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Boid3d>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// vertex of the 3D boid mesh, which points along +z
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+const CONE_SEGMENTS : u32 = 8;
+const CONE_LENGTH : f32 = 0.02;
+const CONE_RADIUS : f32 = 0.006;
+
+// cone with a closed base, flat shaded
+pub fn cone_vertices() -> Vec<MeshVertex> {
+    let tip = [0.0, 0.0, CONE_LENGTH * 0.6];
+    let base_z = -CONE_LENGTH * 0.4;
+    let rim = |i: u32| {
+        let angle = i as f32 / CONE_SEGMENTS as f32 * std::f32::consts::TAU;
+        [CONE_RADIUS * angle.cos(), CONE_RADIUS * angle.sin(), base_z]
+    };
+    let mut vertices = Vec::with_capacity(CONE_SEGMENTS as usize * 6);
+    for i in 0..CONE_SEGMENTS {
+        let (a, b) = (rim(i), rim(i + 1));
+        // side, the normal leans forward by the cone's slope
+        let mid = (i as f32 + 0.5) / CONE_SEGMENTS as f32 * std::f32::consts::TAU;
+        let slope = CONE_RADIUS / CONE_LENGTH;
+        let length = (1.0 + slope * slope).sqrt();
+        let normal = [mid.cos() / length, mid.sin() / length, slope / length];
+        for position in [a, b, tip] {
+            vertices.push(MeshVertex { position, normal });
+        }
+        // base
+        for position in [b, a, [0.0, 0.0, base_z]] {
+            vertices.push(MeshVertex { position, normal: [0.0, 0.0, -1.0] });
+        }
+    }
+    vertices
+}
+
+pub fn mesh_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![2 => Float32x3, 3 => Float32x3];
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &ATTRIBS,
+    }
+}
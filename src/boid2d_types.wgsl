@@ -0,0 +1,6 @@
+// Boid struct that matches Boid in boid.rs
+
+struct Boid {
+    pos: vec2<f32>,
+    vel: vec2<f32>,
+}
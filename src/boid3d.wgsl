@@ -0,0 +1,59 @@
+// Shaders for the 3D modes: instanced cones lit by one directional light
+
+#import camera_types.wgsl
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) world_pos: vec3<f32>,
+    @location(2) @interpolate(flat) leader: u32,
+};
+
+@vertex
+fn boid3d_vs_main(
+    @location(0) instance_pos: vec3<f32>,
+    @location(1) instance_vel: vec3<f32>,
+    @location(2) vertex_pos: vec3<f32>,
+    @location(3) vertex_normal: vec3<f32>,
+    @location(5) flags: u32,
+) -> VertexOutput {
+    // orthonormal basis with the mesh's +z along the velocity
+    var forward = vec3<f32>(0.0, 0.0, 1.0);
+    if (length(instance_vel) > 0.0) {
+        forward = normalize(instance_vel);
+    }
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(forward.y) > 0.99) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let right = normalize(cross(up, forward));
+    let new_up = cross(forward, right);
+    let rotation = mat3x3<f32>(right, new_up, forward);
+
+    // leaders are drawn bigger, see leaders.wgsl for the flags
+    var out: VertexOutput;
+    out.leader = flags & 1u;
+    out.world_pos = instance_pos + rotation * vertex_pos * select(1.0, 1.6, out.leader != 0u);
+    out.normal = rotation * vertex_normal;
+    out.clip_position = camera.view_proj * vec4<f32>(out.world_pos, 1.0);
+    return out;
+}
+
+@fragment
+fn boid3d_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var base_color = vec3<f32>(0.9, 0.6, 0.6); // light pink, like the 2D boids
+    if (in.leader != 0u) {
+        base_color = vec3<f32>(1.0, 0.8, 0.2); // gold
+    }
+    let normal = normalize(in.normal);
+    let light_dir = normalize(camera.light_dir.xyz);
+    let view_dir = normalize(camera.eye.xyz - in.world_pos);
+    let diffuse = max(dot(normal, light_dir), 0.0);
+    let half_dir = normalize(light_dir + view_dir);
+    let specular = pow(max(dot(normal, half_dir), 0.0), 32.0) * 0.3;
+    let ambient = 0.25;
+    return vec4<f32>(base_color * (ambient + diffuse) + vec3<f32>(specular), 1.0);
+}
@@ -0,0 +1,6 @@
+// Boid struct that matches Boid3d in boid.rs, vec3 members are padded to 16 bytes
+
+struct Boid {
+    pos: vec3<f32>,
+    vel: vec3<f32>,
+}
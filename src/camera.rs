@@ -0,0 +1,156 @@
+// Camera for the 3D mode, orbiting around a target point that can also be flown around
+#[derive(Debug, Clone, Copy)]
+pub struct OrbitCamera {
+    pub target: [f32; 3],
+    // radians around the vertical axis, and above the horizontal plane
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    // vertical field of view in radians
+    pub fovy: f32,
+}
+
+// CameraUniform struct that matches camera_types.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+    pub eye: [f32; 4],
+    // direction the light comes from, in world space
+    pub light_dir: [f32; 4],
+}
+
+const MIN_DISTANCE : f32 = 0.2;
+const MAX_DISTANCE : f32 = 20.0;
+// keep away from straight up and down, where the view matrix flips
+const MAX_PITCH : f32 = 1.5;
+const NEAR : f32 = 0.01;
+const FAR : f32 = 100.0;
+
+impl Default for OrbitCamera {
+    // looking at the whole [-1, 1] cube from slightly above
+    fn default() -> Self {
+        Self {
+            target: [0.0, 0.0, 0.0],
+            yaw: 0.6,
+            pitch: 0.4,
+            distance: 3.5,
+            fovy: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+impl OrbitCamera {
+    pub fn eye(&self) -> [f32; 3] {
+        let offset = [
+            self.distance * self.pitch.cos() * self.yaw.sin(),
+            self.distance * self.pitch.sin(),
+            self.distance * self.pitch.cos() * self.yaw.cos(),
+        ];
+        add(self.target, offset)
+    }
+
+    // rotate around the target, in radians
+    pub fn orbit(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        self.yaw -= delta_yaw;
+        self.pitch = (self.pitch + delta_pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // positive amounts move closer
+    pub fn zoom(&mut self, amount: f32) {
+        self.distance = (self.distance * (1.0 - amount)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    // move the target along the view direction, the camera's right and world up
+    pub fn fly(&mut self, forward: f32, right: f32, up: f32) {
+        let eye = self.eye();
+        let view_dir = normalize(sub(self.target, eye));
+        let right_dir = normalize(cross(view_dir, [0.0, 1.0, 0.0]));
+        for (axis, amount) in [(view_dir, forward), (right_dir, right), ([0.0, 1.0, 0.0], up)] {
+            self.target = add(self.target, scale(axis, amount * self.distance));
+        }
+    }
+
+    pub fn uniform(&self, aspect: f32) -> CameraUniform {
+        let eye = self.eye();
+        let view = look_at(eye, self.target, [0.0, 1.0, 0.0]);
+        let proj = perspective(self.fovy, aspect, NEAR, FAR);
+        CameraUniform {
+            view_proj: mul(proj, view),
+            eye: [eye[0], eye[1], eye[2], 1.0],
+            light_dir: [0.4, 0.8, 0.45, 0.0],
+        }
+    }
+}
+
+// column-major matrices, like WGSL's mat4x4
+
+fn look_at(eye: [f32; 3], target: [f32; 3], up: [f32; 3]) -> [[f32; 4]; 4] {
+    let f = normalize(sub(target, eye));
+    let s = normalize(cross(f, up));
+    let u = cross(s, f);
+    [
+        [s[0], u[0], -f[0], 0.0],
+        [s[1], u[1], -f[1], 0.0],
+        [s[2], u[2], -f[2], 0.0],
+        [-dot(s, eye), -dot(u, eye), dot(f, eye), 1.0],
+    ]
+}
+
+// right handed, depth from 0 at `near` to 1 at `far` like wgpu expects
+fn perspective(fovy: f32, aspect: f32, near: f32, far: f32) -> [[f32; 4]; 4] {
+    let f = 1.0 / (fovy / 2.0).tan();
+    [
+        [f / aspect, 0.0, 0.0, 0.0],
+        [0.0, f, 0.0, 0.0],
+        [0.0, 0.0, far / (near - far), -1.0],
+        [0.0, 0.0, near * far / (near - far), 0.0],
+    ]
+}
+
+fn mul(a: [[f32; 4]; 4], b: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut out = [[0.0; 4]; 4];
+    for (col, out_col) in out.iter_mut().enumerate() {
+        for (row, value) in out_col.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b[col][k]).sum();
+        }
+    }
+    out
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = dot(a, a).sqrt();
+    if length > 0.0 { scale(a, 1.0 / length) } else { a }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::{assert_layout, offsets};
+
+    #[test]
+    fn camera_uniform_matches_the_shader() {
+        let fields = offsets!(CameraUniform { view_proj, eye, light_dir });
+        assert_layout("camera_types.wgsl", "Camera", fields, Some(std::mem::size_of::<CameraUniform>()));
+    }
+}
@@ -0,0 +1,7 @@
+// CameraUniform struct that matches camera.rs
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+    light_dir: vec4<f32>,
+}
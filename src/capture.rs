@@ -0,0 +1,197 @@
+use std::{fs::File, io::{self, BufWriter}, path::Path, sync::mpsc, time::{SystemTime, UNIX_EPOCH}};
+
+// Texture that a frame can be rendered into instead of the window, then copied back to the CPU
+pub struct OffscreenTarget {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OffscreenTarget {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat) -> io::Result<Self> {
+        let max_side = device.limits().max_texture_dimension_2d;
+        if width == 0 || height == 0 || width > max_side || height > max_side {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("capture size {}x{} must be between 1 and {} on each side", width, height, max_side),
+            ));
+        }
+        let padded_size = padded_bytes_per_row(width) as u64 * height as u64;
+        if padded_size > device.limits().max_buffer_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("capture size {}x{} needs a {} byte readback buffer, more than the device allows", width, height, padded_size),
+            ));
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // rendered to like the surface, and copied out for readback
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(Self { texture, view, width, height })
+    }
+
+    // copy the texture into a mapped staging buffer and return tightly packed RGBA8 pixels.
+    // Blocks until the GPU has finished everything submitted so far.
+    pub fn read_rgba(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> io::Result<Vec<u8>> {
+        let format = self.texture.format();
+        let swap_red_blue = match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("can't read back {:?} textures", format),
+                ));
+            }
+        };
+
+        // rows of a texture copy have to start on a 256 byte boundary
+        let unpadded_bytes_per_row = self.width * 4;
+        let padded_bytes_per_row = padded_bytes_per_row(self.width);
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Staging Buffer"),
+            size: padded_bytes_per_row as u64 * self.height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        staging_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::Wait).map_err(io::Error::other)?;
+        receiver.recv()
+            .map_err(io::Error::other)?
+            .map_err(io::Error::other)?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * self.height) as usize);
+        {
+            let data = staging_buffer.slice(..).get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        staging_buffer.unmap();
+
+        if swap_red_blue {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(pixels)
+    }
+}
+
+fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+pub fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // the surface formats we render to are sRGB encoded already
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// e.g. "screenshot-2026-10-18_20-49-22-417.png", using UTC so no timezone database is needed.
+// The milliseconds keep two saves within the same second from overwriting each other
+pub fn timestamped_filename(prefix: &str, extension: &str) -> String {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    let secs = millis / 1000;
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time_of_day = secs % 86400;
+    format!(
+        "{}-{:04}-{:02}-{:02}_{:02}-{:02}-{:02}-{:03}.{}",
+        prefix, year, month, day,
+        time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60, millis % 1000,
+        extension,
+    )
+}
+
+// days since 1970-01-01 to a (year, month, day) date, Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_padded_to_the_copy_alignment() {
+        // 64 pixels are exactly 256 bytes, one more pixel needs another 256
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+        assert_eq!(padded_bytes_per_row(1), 256);
+        assert_eq!(padded_bytes_per_row(1920), 7680);
+    }
+
+    #[test]
+    fn days_map_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        assert_eq!(civil_from_days(20088), (2024, 12, 31));
+        assert_eq!(civil_from_days(20089), (2025, 1, 1));
+    }
+
+    #[test]
+    fn filenames_carry_the_date_and_time() {
+        let name = timestamped_filename("screenshot", "png");
+        // "screenshot-YYYY-MM-DD_HH-MM-SS-mmm.png"
+        assert_eq!(name.len(), "screenshot-2026-10-18_20-49-22-417.png".len());
+        assert!(name.starts_with("screenshot-") && name.ends_with(".png"));
+        assert_eq!(&name[21..22], "_");
+        assert_eq!(&name[30..31], "-");
+    }
+
+    #[test]
+    fn saves_a_moment_apart_get_different_names() {
+        let first = timestamped_filename("screenshot", "png");
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert_ne!(first, timestamped_filename("screenshot", "png"));
+    }
+}
@@ -1,97 +1,356 @@
-// Compute shader
+// Compute shader of the 2D mode
 
-// Boid struct that matches buffer in rust
+#import boid2d_types.wgsl
+#import params_types.wgsl
+#import debug_types.wgsl
+#import math.wgsl
+#import random.wgsl
+#import flight.wgsl
+#import models.wgsl
+#import flow.wgsl
+#import forces.wgsl
+#import paths.wgsl
+#import arena.wgsl
+#import leaders.wgsl
+#import alive.wgsl
 
-struct Boid {
-    pos: vec2<f32>,
-    vel: vec2<f32>,
-}
-
-// constan values -> TODO: move this to parameters
-const DISTANCE : f32 = 0.1;
-const DELTA_T : f32 = 0.03; // smaller step, smoother motion
-const SEPARATION_DISTANCE : f32 = 0.02; // smaller separation distance 
-const COHESION_WEIGHT : f32 = 0.3; // gentle pull towar center 
-const ALIGNMENT_WEIGHT : f32 = 0.4; // a bit more to match velocity
-const SEPARATION_WEIGHT : f32 = 0.7; // stronger force to avoid overlap
-const MAX_SPEED : f32 = 0.4; // keep boids from moving too fast
 // Storage buffer - input. out
 
 @group(0) @binding(0)
 var<storage, read> boid_in: array<Boid>;
 @group(0) @binding(1)
 var<storage, read_write> boid_out: array<Boid>;
+// only the invocation of the selected boid writes to this
+@group(0) @binding(2)
+var<storage, read_write> debug_info: DebugInfo;
+@group(0) @binding(3)
+var<uniform> params: SimParams;
+// the grid built by grid.wgsl, only read in topological mode
+@group(0) @binding(4)
+var<storage, read> cell_start: array<u32>;
+@group(0) @binding(5)
+var<storage, read> sorted: array<u32>;
 
-// compute entry point
+// must match GRID_DIM_2D in grid.rs
+const GRID_DIM : i32 = 128;
 
-// workgroup_size tells the dimension of the workgroup's local grid of invocation
-// each workgroup has 64 threads
-@compute @workgroup_size(64) 
-fn main(@builtin(global_invocation_id) id: vec3<u32>) {
-    let i = id.x;
-    let total = arrayLength(&boid_in);
-    if (i >= total) {
-        return;
+// the k nearest neighbors found by `find_nearest`, closest first
+var<private> nearest: array<u32, MAX_K_NEIGHBORS>;
+var<private> nearest_dist: array<f32, MAX_K_NEIGHBORS>;
+
+// weight of a neighbor at `dist` for a rule with `radius`: 1 up close, fading to 0 at the radius
+fn falloff(kind: u32, dist: f32, radius: f32) -> f32 {
+    if (dist > radius || radius <= 0.0) {
+        return 0.0;
     }
-    let current_boid = boid_in[i];
-    var count : f32 = 0.0;
+    let x = dist / radius;
+    switch kind {
+        case FALLOFF_LINEAR: {
+            return 1.0 - x;
+        }
+        case FALLOFF_SMOOTHSTEP: {
+            return 1.0 - smoothstep(0.0, 1.0, x);
+        }
+        case FALLOFF_INVERSE_SQUARE: {
+            return (1.0 / max(x * x, 0.01) - 1.0) / 99.0;
+        }
+        default: {
+            return 1.0;
+        }
+    }
+}
+
+// cosine of the angle between the boid's heading and the direction to `other_pos`
+fn view_cos(boid: Boid, other_pos: vec2<f32>, dist: f32) -> f32 {
+    let heading_len = length(boid.vel);
+    if (heading_len == 0.0 || dist == 0.0) {
+        return 1.0;
+    }
+    return clamp(dot(boid.vel, other_pos - boid.pos) / (heading_len * dist), -1.0, 1.0);
+}
+
+// Walk the grid in square rings around the boid's cell, keeping the k closest boids outside the blind spot.
+// Everything in ring r is at least r - 1 cells away, so once k are found the search stops at the first
+// ring that can't hold anything closer. Returns how many were found.
+fn find_nearest(i: u32, boid: Boid, k: u32, blind_cos: f32) -> u32 {
+    let cell_size = 2.0 / f32(GRID_DIM);
+    let cell = vec2<i32>(clamp((boid.pos * 0.5 + 0.5) * f32(GRID_DIM), vec2<f32>(0.0), vec2<f32>(f32(GRID_DIM) - 1.0)));
+    var found = 0u;
+    for (var ring = 0; ring < GRID_DIM; ring++) {
+        if (found == k && nearest_dist[k - 1u] <= f32(ring - 1) * cell_size) {
+            break;
+        }
+        for (var dy = -ring; dy <= ring; dy++) {
+            // rows in the middle of the ring only have its left and right cell
+            let dx_step = select(2 * ring, 1, abs(dy) == ring || ring == 0);
+            for (var dx = -ring; dx <= ring; dx += dx_step) {
+                let c = cell + vec2<i32>(dx, dy);
+                if (any(c < vec2<i32>(0)) || any(c >= vec2<i32>(GRID_DIM))) {
+                    continue;
+                }
+                let index = u32(c.y * GRID_DIM + c.x);
+                for (var s = cell_start[index]; s < cell_start[index + 1u]; s++) {
+                    let j = sorted[s];
+                    let other_pos = boid_in[j].pos;
+                    let dist = distance(other_pos, boid.pos);
+                    if (j == i || (found == k && dist >= nearest_dist[k - 1u])) {
+                        continue;
+                    }
+                    if (params.blind_spot > 0.0 && view_cos(boid, other_pos, dist) < blind_cos) {
+                        continue;
+                    }
+                    // insertion sort, dropping the furthest when full
+                    var slot = min(found, k - 1u);
+                    while (slot > 0u && nearest_dist[slot - 1u] > dist) {
+                        nearest[slot] = nearest[slot - 1u];
+                        nearest_dist[slot] = nearest_dist[slot - 1u];
+                        slot--;
+                    }
+                    nearest[slot] = j;
+                    nearest_dist[slot] = dist;
+                    found = min(found + 1u, k);
+                }
+            }
+        }
+    }
+    return found;
+}
+
+// neighbor `n` of the candidates, for models.wgsl. The plane is flat, it looks the same from any `pos`
+fn neighbor(n: u32, ahead: f32, pos: vec3<f32>) -> Neighbor {
+    var j = n;
+    if (params.neighbor_mode == NEIGHBORS_TOPOLOGICAL) {
+        j = nearest[n];
+    }
+    let other = boid_in[j];
+    return Neighbor(j, vec3<f32>(other.pos + other.vel * ahead, 0.0), vec3<f32>(other.vel, 0.0));
+}
+
+// The Reynolds rules for boid `i` at `boid` when its neighbors have flown on for `ahead` seconds,
+// as the velocity change of a step. `flocking` scales alignment and cohesion.
+// `record` fills in the debug buffer when `i` is the selected boid
+fn reynolds_steering(i: u32, boid: Boid, ahead: f32, candidates: u32, flocking: f32, record: bool) -> vec2<f32> {
+    // total weight of the neighbors each average is taken over
+    var alignment_total : f32 = 0.0;
+    var cohesion_total : f32 = 0.0;
+    let perception = max(params.separation_distance, max(params.alignment_distance, params.cohesion_distance));
+    // vision cone, compared as cosines of the angle between heading and neighbor direction
+    let fov_cos = cos(params.fov * 0.5);
+    let blind_cos = -cos(params.blind_spot * 0.5);
     var avg_alignment = vec2<f32>(0.0, 0.0);
     var avg_cohesion = vec2<f32>(0.0, 0.0);
     var avg_separation = vec2<f32>(0.0, 0.0);
+    let is_selected = record && i == debug_info.selected;
+    var debug_count = 0u;
 
-    for (var j: u32 = 0u; j < total; j++) {
+    // metric mode looks at every boid, topological mode only at the k nearest
+    let topological = params.neighbor_mode == NEIGHBORS_TOPOLOGICAL;
+    for (var n: u32 = 0u; n < candidates; n++) {
+        var j = n;
+        if (topological) {
+            j = nearest[n];
+        }
         if (i == j) {
             // ignore its own boid
             continue; 
         }
 
-        let other = boid_in[j];
-        let dist = distance(other.pos, current_boid.pos);
-        if (dist > DISTANCE) {
+        var other = boid_in[j];
+        other.pos += other.vel * ahead;
+        let dist = distance(other.pos, boid.pos);
+        if (!topological && dist > perception) {
+            continue;
+        }
+        let cos_angle = view_cos(boid, other.pos, dist);
+        // nothing is seen in the blind spot behind the boid
+        if (params.blind_spot > 0.0 && cos_angle < blind_cos) {
             continue;
         }
-        if (dist <= SEPARATION_DISTANCE && dist != 0.0) {
-        // separation: remove avg position of surrounding boids
-            avg_separation -= (other.pos - current_boid.pos); 
+        // separation: push away from close boids, the closer the harder depending on the falloff
+        let separation = falloff(params.separation_falloff, dist, params.separation_distance);
+        if (separation > 0.0 && dist != 0.0) {
+            avg_separation -= (other.pos - boid.pos) * separation; 
         }
+        // alignment and cohesion only count neighbors inside the field of view
+        if (params.fov < TAU && cos_angle < fov_cos) {
+            continue;
+        }
+        // the k nearest count fully in topological mode, there is no radius to fade over
+        var alignment = 1.0;
+        var cohesion = 1.0;
+        if (!topological) {
+            alignment = falloff(params.alignment_falloff, dist, params.alignment_distance);
+            cohesion = falloff(params.cohesion_falloff, dist, params.cohesion_distance);
+        }
+        // leaders count more than their peers
+        let lead = leader_weight(j);
+        alignment *= lead;
+        cohesion *= lead;
         // Alignment : add avg velocity of the surrounding boids
-        avg_alignment += other.vel;
+        avg_alignment += other.vel * alignment;
+        alignment_total += alignment;
 
         // Cohesion: add avg position of surrounding boids
-        avg_cohesion += other.pos;
+        avg_cohesion += other.pos * cohesion;
+        cohesion_total += cohesion;
 
-        count += 1.0;
+        if (is_selected && (alignment > 0.0 || cohesion > 0.0) && debug_count < MAX_DEBUG_NEIGHBORS) {
+            debug_info.neighbors[debug_count] = other.pos;
+            debug_count++;
+        }
     }
-    // divide forces by count and limit speed
-    if (count > 0.0) {
-        // avg_separation = normalize(avg_separation / count);
-        avg_alignment = normalize(avg_alignment / count);
-        avg_cohesion = normalize(avg_cohesion / count) - current_boid.pos;
+    // divide forces by the total weight
+    // neighbors at a standstill or centered on the origin have no direction to give
+    if (alignment_total > 0.0) {
+        avg_alignment = safe_normalize(vec3<f32>(avg_alignment / alignment_total, 0.0), vec3<f32>(0.0)).xy;
     }
-    let acc = avg_cohesion * COHESION_WEIGHT + avg_alignment * ALIGNMENT_WEIGHT + avg_separation * SEPARATION_WEIGHT;
-    var vel = current_boid.vel + acc;
-    
-    // if (length(vel) > max_speed) {
-    //     vel = normalize(vel) * max_speed;
-    // }
-    vel = normalize(vel) * clamp(length(vel), 0.0, MAX_SPEED);
-    var pos = current_boid.pos + (vel * DELTA_T);
+    if (cohesion_total > 0.0) {
+        avg_cohesion = safe_normalize(vec3<f32>(avg_cohesion / cohesion_total, 0.0), vec3<f32>(0.0)).xy - boid.pos;
+    }
+    if (is_selected) {
+        debug_info.neighbor_count = debug_count;
+        // in topological mode the circle reaches the furthest of the k nearest
+        debug_info.perception_radius = perception;
+        if (topological) {
+            debug_info.perception_radius = select(0.0, nearest_dist[candidates - 1u], candidates > 0u);
+        }
+        debug_info.separation_radius = params.separation_distance;
+        debug_info.pos = boid.pos;
+        debug_info.vel = boid.vel;
+        debug_info.separation = avg_separation * params.separation_weight;
+        debug_info.alignment = avg_alignment * params.alignment_weight;
+        debug_info.cohesion = avg_cohesion * params.cohesion_weight;
+        debug_info.fov = params.fov;
+        debug_info.blind_spot = params.blind_spot;
+    }
+    return (avg_cohesion * params.cohesion_weight + avg_alignment * params.alignment_weight) * flocking
+        + avg_separation * params.separation_weight;
+}
 
-    // limit by the screen
-    if (pos.x > 1.0) {
-        pos.x = -1.0;
+// Everything steering boid `i` when it's at `boid` and its neighbors have flown on for `ahead` seconds,
+// as an acceleration: the model picked for this pipeline, then leaders, flow, forces, paths and wander.
+// The neighbors are the first `candidates` boids, or the k nearest in topological mode
+fn steering(i: u32, boid: Boid, ahead: f32, candidates: u32, record: bool) -> vec2<f32> {
+    let total = population.slots;
+    let pos = vec3<f32>(boid.pos, 0.0);
+    let vel = vec3<f32>(boid.vel, 0.0);
+    // leaders mostly go their own way
+    var flocking = 1.0;
+    var leading = vec2<f32>(0.0);
+    if (is_leader(i)) {
+        flocking = leaders.flocking;
+        leading = leader_steering(i, pos, vel, false).xy;
+    }
+    var social: vec2<f32>;
+    switch MODEL {
+        case MODEL_VICSEK: {
+            social = vicsek_steering(i, pos, vel, ahead, candidates, false).xy * flocking;
+        }
+        case MODEL_COUZIN: {
+            social = couzin_steering(i, pos, vel, ahead, candidates, false).xy * flocking;
+        }
+        case MODEL_CUCKER_SMALE: {
+            social = cucker_smale_steering(i, pos, vel, ahead, candidates).xy * flocking;
+        }
+        case MODEL_DORSOGNA: {
+            social = dorsogna_steering(i, pos, vel, ahead, candidates).xy * flocking;
+        }
+        default: {
+            social = reynolds_steering(i, boid, ahead, candidates, flocking, record) * STEERING_RATE;
+        }
     }
-    if (pos.x <-1.0) {
-        pos.x = 1.0;
+    if (EXTRA_FORCE) {
+        social += extra_steering(i, boid, ahead, candidates) * STEERING_RATE;
     }
-    if (pos.y > 1.0) {
-        pos.y = -1.0;
+    // the other models have no rule vectors to show, only the boid itself
+    if (MODEL != MODEL_REYNOLDS && record && i == debug_info.selected) {
+        debug_info.neighbor_count = 0u;
+        debug_info.pos = boid.pos;
+        debug_info.vel = boid.vel;
+        debug_info.separation = vec2<f32>(0.0);
+        debug_info.alignment = vec2<f32>(0.0);
+        debug_info.cohesion = vec2<f32>(0.0);
     }
-    if (pos.y <-1.0) {
-        pos.y = 1.0;
+    let acc = leading
+        + flow_at(pos, false).xy * flow.strength
+        + force_at(pos).xy
+        + path_steering(i, total, pos, vel).xy
+        + wander_steering(i, false).xy;
+    // the walls push as an acceleration
+    return limit_force(vec3<f32>(social + acc * STEERING_RATE + wall_steering(boid.pos), 0.0)).xy;
+}
+
+// the user's extra_force summed over the neighbors within perception, as the velocity change of a step
+fn extra_steering(i: u32, boid: Boid, ahead: f32, candidates: u32) -> vec2<f32> {
+    let perception = max(params.separation_distance, max(params.alignment_distance, params.cohesion_distance));
+    let pos = vec3<f32>(boid.pos, 0.0);
+    var sum = vec2<f32>(0.0);
+    for (var n = 0u; n < candidates; n++) {
+        let other = neighbor(n, ahead, vec3<f32>(boid.pos, 0.0));
+        let dist = distance(other.pos, pos);
+        if (other.index == i || !in_reach(dist, perception)) {
+            continue;
+        }
+        sum += extra_force(boid, Boid(other.pos.xy, other.vel.xy), dist);
     }
+    return sum;
+}
 
-    boid_out[i] = Boid(pos, vel);
+// velocity of boid `i` after accelerating by `acc` for `dt`
+fn fly_2d(i: u32, vel: vec2<f32>, acc: vec2<f32>, dt: f32) -> vec2<f32> {
+    return fly(i, vec3<f32>(vel, 0.0), vec3<f32>(acc, 0.0), dt, false).xy;
 }
 
+// compute entry point
+
+// workgroup_size tells the dimension of the workgroup's local grid of invocation
+// each workgroup has 64 threads
+@compute @workgroup_size(64) 
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    // the buffers have room for more boids than are in use
+    let total = population.slots;
+    if (i >= total) {
+        return;
+    }
+    let current_boid = boid_in[i];
+    // dead boids stay parked, far from every neighbor, until a new boid takes their slot
+    if (!is_alive(i)) {
+        boid_out[i] = current_boid;
+        return;
+    }
+    // the neighbors are picked once, at the start of the step
+    var candidates = total;
+    if (params.neighbor_mode == NEIGHBORS_TOPOLOGICAL) {
+        candidates = find_nearest(i, current_boid, clamp(params.k_neighbors, 1u, MAX_K_NEIGHBORS), -cos(params.blind_spot * 0.5));
+    }
+
+    // one step of the integrator, see flight.wgsl
+    let dt = params.delta_t;
+    let pos = current_boid.pos;
+    let vel = current_boid.vel;
+    let acc = steering(i, current_boid, 0.0, candidates, true);
+    var next: Boid;
+    switch params.integrator {
+        case INTEGRATOR_EXPLICIT_EULER: {
+            next = Boid(pos + vel * dt, fly_2d(i, vel, acc, dt));
+        }
+        case INTEGRATOR_VELOCITY_VERLET: {
+            let new_pos = pos + vel * dt + acc * (0.5 * dt * dt);
+            let new_acc = steering(i, Boid(new_pos, vel), dt, candidates, false);
+            next = Boid(new_pos, fly_2d(i, vel, (acc + new_acc) * 0.5, dt));
+        }
+        case INTEGRATOR_RK2: {
+            let mid_vel = fly_2d(i, vel, acc, dt * 0.5);
+            let mid_acc = steering(i, Boid(pos + vel * (dt * 0.5), mid_vel), dt * 0.5, candidates, false);
+            next = Boid(pos + mid_vel * dt, fly_2d(i, vel, mid_acc, dt));
+        }
+        default: {
+            let new_vel = fly_2d(i, vel, acc, dt);
+            next = Boid(pos + new_vel * dt, new_vel);
+        }
+    }
 
+    // wrap around the screen or stay within the walls
+    boid_out[i] = contain(next);
+}
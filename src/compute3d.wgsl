@@ -0,0 +1,291 @@
+// Compute shader for the 3D modes, the same rules as compute.wgsl with vec3 boids,
+// in the cube or on the sphere (see sphere.wgsl)
+
+#import boid3d_types.wgsl
+#import params_types.wgsl
+#import math.wgsl
+#import random.wgsl
+#import flight.wgsl
+#import sphere.wgsl
+#import models.wgsl
+#import flow.wgsl
+#import forces.wgsl
+#import paths.wgsl
+#import leaders.wgsl
+#import alive.wgsl
+
+// Storage buffer - input. out
+// binding 2 is the debug buffer, the overlay only exists in 2D so it isn't used here
+
+@group(0) @binding(0)
+var<storage, read> boid_in: array<Boid>;
+@group(0) @binding(1)
+var<storage, read_write> boid_out: array<Boid>;
+@group(0) @binding(3)
+var<uniform> params: SimParams;
+// the grid built by grid.wgsl, only read in topological mode
+@group(0) @binding(4)
+var<storage, read> cell_start: array<u32>;
+@group(0) @binding(5)
+var<storage, read> sorted: array<u32>;
+
+// must match GRID_DIM_3D in grid.rs
+const GRID_DIM : i32 = 32;
+
+var<private> nearest: array<u32, MAX_K_NEIGHBORS>;
+var<private> nearest_dist: array<f32, MAX_K_NEIGHBORS>;
+
+// weight of a neighbor at `dist` for a rule with `radius`: 1 up close, fading to 0 at the radius
+fn falloff(kind: u32, dist: f32, radius: f32) -> f32 {
+    if (dist > radius || radius <= 0.0) {
+        return 0.0;
+    }
+    let x = dist / radius;
+    switch kind {
+        case FALLOFF_LINEAR: {
+            return 1.0 - x;
+        }
+        case FALLOFF_SMOOTHSTEP: {
+            return 1.0 - smoothstep(0.0, 1.0, x);
+        }
+        case FALLOFF_INVERSE_SQUARE: {
+            return (1.0 / max(x * x, 0.01) - 1.0) / 99.0;
+        }
+        default: {
+            return 1.0;
+        }
+    }
+}
+
+// cosine of the angle between the boid's heading and the direction to `other_pos`
+fn view_cos(boid: Boid, other_pos: vec3<f32>, dist: f32) -> f32 {
+    let heading_len = length(boid.vel);
+    if (heading_len == 0.0 || dist == 0.0) {
+        return 1.0;
+    }
+    return clamp(dot(boid.vel, other_pos - boid.pos) / (heading_len * dist), -1.0, 1.0);
+}
+
+// like find_nearest in compute.wgsl, walking cube shaped shells of cells instead of square rings
+fn find_nearest(i: u32, boid: Boid, k: u32, blind_cos: f32) -> u32 {
+    let cell_size = 2.0 / f32(GRID_DIM);
+    let cell = vec3<i32>(clamp((boid.pos * 0.5 + 0.5) * f32(GRID_DIM), vec3<f32>(0.0), vec3<f32>(f32(GRID_DIM) - 1.0)));
+    var found = 0u;
+    for (var ring = 0; ring < GRID_DIM; ring++) {
+        if (found == k && nearest_dist[k - 1u] <= f32(ring - 1) * cell_size) {
+            break;
+        }
+        for (var dz = -ring; dz <= ring; dz++) {
+            for (var dy = -ring; dy <= ring; dy++) {
+                // inside the shell only the first and last cell of each row are on its surface
+                let dx_step = select(2 * ring, 1, max(abs(dy), abs(dz)) == ring || ring == 0);
+                for (var dx = -ring; dx <= ring; dx += dx_step) {
+                    let c = cell + vec3<i32>(dx, dy, dz);
+                    if (any(c < vec3<i32>(0)) || any(c >= vec3<i32>(GRID_DIM))) {
+                        continue;
+                    }
+                    let index = u32((c.z * GRID_DIM + c.y) * GRID_DIM + c.x);
+                    for (var s = cell_start[index]; s < cell_start[index + 1u]; s++) {
+                        let j = sorted[s];
+                        let other_pos = boid_in[j].pos;
+                        // the straight line distance, on the sphere it orders the neighbors like the geodesic one
+                        let dist = distance(other_pos, boid.pos);
+                        if (j == i || (found == k && dist >= nearest_dist[k - 1u])) {
+                            continue;
+                        }
+                        if (params.blind_spot > 0.0) {
+                            let seen = seen_at(boid.pos, other_pos);
+                            if (view_cos(boid, seen, distance(seen, boid.pos)) < blind_cos) {
+                                continue;
+                            }
+                        }
+                        var slot = min(found, k - 1u);
+                        while (slot > 0u && nearest_dist[slot - 1u] > dist) {
+                            nearest[slot] = nearest[slot - 1u];
+                            nearest_dist[slot] = nearest_dist[slot - 1u];
+                            slot--;
+                        }
+                        nearest[slot] = j;
+                        nearest_dist[slot] = dist;
+                        found = min(found + 1u, k);
+                    }
+                }
+            }
+        }
+    }
+    return found;
+}
+
+// neighbor `n` of the candidates as seen from `pos`, for models.wgsl
+fn neighbor(n: u32, ahead: f32, pos: vec3<f32>) -> Neighbor {
+    var j = n;
+    if (params.neighbor_mode == NEIGHBORS_TOPOLOGICAL) {
+        j = nearest[n];
+    }
+    let other = boid_in[j];
+    return Neighbor(j, seen_at(pos, other.pos + other.vel * ahead), seen_heading(pos, other.vel));
+}
+
+// like reynolds_steering in compute.wgsl, without the debug buffer
+fn reynolds_steering(i: u32, boid: Boid, ahead: f32, candidates: u32, flocking: f32) -> vec3<f32> {
+    // total weight of the neighbors each average is taken over
+    var alignment_total : f32 = 0.0;
+    var cohesion_total : f32 = 0.0;
+    let perception = max(params.separation_distance, max(params.alignment_distance, params.cohesion_distance));
+    // vision cone, compared as cosines of the angle between heading and neighbor direction
+    let fov_cos = cos(params.fov * 0.5);
+    let blind_cos = -cos(params.blind_spot * 0.5);
+    var avg_alignment = vec3<f32>(0.0);
+    var avg_cohesion = vec3<f32>(0.0);
+    var avg_separation = vec3<f32>(0.0);
+
+    let topological = params.neighbor_mode == NEIGHBORS_TOPOLOGICAL;
+    for (var n: u32 = 0u; n < candidates; n++) {
+        let other = neighbor(n, ahead, boid.pos);
+        if (other.index == i) {
+            continue;
+        }
+        let dist = distance(other.pos, boid.pos);
+        if (!topological && dist > perception) {
+            continue;
+        }
+        let cos_angle = view_cos(boid, other.pos, dist);
+        // nothing is seen in the blind spot behind the boid
+        if (params.blind_spot > 0.0 && cos_angle < blind_cos) {
+            continue;
+        }
+        let separation = falloff(params.separation_falloff, dist, params.separation_distance);
+        if (separation > 0.0 && dist != 0.0) {
+            avg_separation -= (other.pos - boid.pos) * separation;
+        }
+        if (params.fov < TAU && cos_angle < fov_cos) {
+            continue;
+        }
+        var alignment = 1.0;
+        var cohesion = 1.0;
+        if (!topological) {
+            alignment = falloff(params.alignment_falloff, dist, params.alignment_distance);
+            cohesion = falloff(params.cohesion_falloff, dist, params.cohesion_distance);
+        }
+        // leaders count more than their peers
+        let lead = leader_weight(other.index);
+        alignment *= lead;
+        cohesion *= lead;
+        avg_alignment += other.vel * alignment;
+        alignment_total += alignment;
+        avg_cohesion += other.pos * cohesion;
+        cohesion_total += cohesion;
+    }
+    // neighbors at a standstill or centered on the origin have no direction to give
+    if (alignment_total > 0.0) {
+        avg_alignment = safe_normalize(avg_alignment / alignment_total, vec3<f32>(0.0));
+    }
+    if (cohesion_total > 0.0) {
+        avg_cohesion = safe_normalize(avg_cohesion / cohesion_total, vec3<f32>(0.0)) - boid.pos;
+    }
+    return (avg_cohesion * params.cohesion_weight + avg_alignment * params.alignment_weight) * flocking
+        + avg_separation * params.separation_weight;
+}
+
+// like extra_steering in compute.wgsl
+fn extra_steering(i: u32, boid: Boid, ahead: f32, candidates: u32) -> vec3<f32> {
+    let perception = max(params.separation_distance, max(params.alignment_distance, params.cohesion_distance));
+    var sum = vec3<f32>(0.0);
+    for (var n = 0u; n < candidates; n++) {
+        let other = neighbor(n, ahead, boid.pos);
+        let dist = distance(other.pos, boid.pos);
+        if (other.index == i || !in_reach(dist, perception)) {
+            continue;
+        }
+        sum += extra_force(boid, Boid(other.pos, other.vel), dist);
+    }
+    return sum;
+}
+
+// like steering in compute.wgsl
+fn steering(i: u32, boid: Boid, ahead: f32, candidates: u32) -> vec3<f32> {
+    let total = population.slots;
+    // leaders mostly go their own way
+    var flocking = 1.0;
+    var leading = vec3<f32>(0.0);
+    if (is_leader(i)) {
+        flocking = leaders.flocking;
+        leading = leader_steering(i, boid.pos, boid.vel, true);
+    }
+    var social: vec3<f32>;
+    switch MODEL {
+        case MODEL_VICSEK: {
+            social = vicsek_steering(i, boid.pos, boid.vel, ahead, candidates, true) * flocking;
+        }
+        case MODEL_COUZIN: {
+            social = couzin_steering(i, boid.pos, boid.vel, ahead, candidates, true) * flocking;
+        }
+        case MODEL_CUCKER_SMALE: {
+            social = cucker_smale_steering(i, boid.pos, boid.vel, ahead, candidates) * flocking;
+        }
+        case MODEL_DORSOGNA: {
+            social = dorsogna_steering(i, boid.pos, boid.vel, ahead, candidates) * flocking;
+        }
+        default: {
+            social = reynolds_steering(i, boid, ahead, candidates, flocking) * STEERING_RATE;
+        }
+    }
+    if (EXTRA_FORCE) {
+        social += extra_steering(i, boid, ahead, candidates) * STEERING_RATE;
+    }
+    let acc = leading
+        + flow_at(boid.pos, true) * flow.strength + force_at(boid.pos)
+        + path_steering(i, total, boid.pos, boid.vel)
+        + wander_steering(i, true);
+    // on the sphere only the part along the surface is flown
+    return limit_force(tangent(boid.pos, social + acc * STEERING_RATE));
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    // the buffers have room for more boids than are in use
+    let total = population.slots;
+    if (i >= total) {
+        return;
+    }
+    // dead boids stay parked, far from every neighbor, until a new boid takes their slot
+    if (!is_alive(i)) {
+        boid_out[i] = boid_in[i];
+        return;
+    }
+    // boids spawned off the sphere join it on their first step
+    let current_boid = settle(boid_in[i]);
+    var candidates = total;
+    if (params.neighbor_mode == NEIGHBORS_TOPOLOGICAL) {
+        candidates = find_nearest(i, current_boid, clamp(params.k_neighbors, 1u, MAX_K_NEIGHBORS), -cos(params.blind_spot * 0.5));
+    }
+
+    // one step of the integrator, see flight.wgsl
+    let dt = params.delta_t;
+    let pos = current_boid.pos;
+    let vel = current_boid.vel;
+    let acc = steering(i, current_boid, 0.0, candidates);
+    var next: Boid;
+    switch params.integrator {
+        case INTEGRATOR_EXPLICIT_EULER: {
+            next = Boid(pos + vel * dt, fly(i, vel, acc, dt, true));
+        }
+        case INTEGRATOR_VELOCITY_VERLET: {
+            let new_pos = pos + vel * dt + acc * (0.5 * dt * dt);
+            let new_acc = steering(i, Boid(on_surface(new_pos), vel), dt, candidates);
+            next = Boid(new_pos, fly(i, vel, (acc + new_acc) * 0.5, dt, true));
+        }
+        case INTEGRATOR_RK2: {
+            let mid_vel = fly(i, vel, acc, dt * 0.5, true);
+            let mid_acc = steering(i, Boid(on_surface(pos + vel * (dt * 0.5)), mid_vel), dt * 0.5, candidates);
+            next = Boid(pos + mid_vel * dt, fly(i, vel, mid_acc, dt, true));
+        }
+        default: {
+            let new_vel = fly(i, vel, acc, dt, true);
+            next = Boid(pos + new_vel * dt, new_vel);
+        }
+    }
+
+    boid_out[i] = settle(next);
+}
@@ -0,0 +1,233 @@
+use std::sync::mpsc;
+
+use crate::{boid::Boid, shaders};
+
+// maximum number of neighbor positions the compute kernel records for the selected boid
+pub const MAX_DEBUG_NEIGHBORS: u32 = 512;
+// selected, neighbor_count, perception & separation radius, then pos, vel, the three rule contributions,
+// field of view and blind spot
+const DEBUG_HEADER_SIZE: u64 = 64;
+pub const DEBUG_BUFFER_SIZE: u64 = DEBUG_HEADER_SIZE + MAX_DEBUG_NEIGHBORS as u64 * 8;
+// two circles, three arrows, the vision cone edges and one line per neighbor, drawn as a line list
+const CIRCLE_SEGMENTS: u32 = 64;
+const ARROW_VERTICES: u32 = 6;
+const CONE_EDGE_VERTICES: u32 = 8;
+const DEBUG_VERTEX_COUNT: u32 = 4 * CIRCLE_SEGMENTS + 3 * ARROW_VERTICES + CONE_EDGE_VERTICES + 2 * MAX_DEBUG_NEIGHBORS;
+// value written to `selected` when nothing is selected, no boid index can match it
+const NO_SELECTION: u32 = u32::MAX;
+// how far (in world units) a click may be from a boid to select it
+const PICK_RADIUS: f32 = 0.05;
+
+// Overlay showing what the compute kernel saw for one selected boid.
+// The kernel fills the debug buffer for the boid whose index matches `selected`,
+// and the debug pipeline turns that buffer into lines without any CPU readback.
+pub struct DebugOverlay {
+    pub buffer: wgpu::Buffer,
+    pub selected: Option<u32>,
+    render_pipeline: wgpu::RenderPipeline,
+    render_bind_group: wgpu::BindGroup,
+    // picking copies the boids into this buffer and searches them on the CPU once mapped
+    pick_buffer: wgpu::Buffer,
+    // boids the pick buffer has room for, no copy or read goes past them
+    max_instances: u32,
+    pick: Pick,
+}
+
+// a pick goes through these steps over a couple of frames, carrying the clicked world position
+enum Pick {
+    Idle,
+    Requested([f32; 2]),
+    Copied([f32; 2]),
+    Mapping([f32; 2], mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+impl DebugOverlay {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, max_instances: u32) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Buffer"),
+            size: DEBUG_BUFFER_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let pick_buffer = create_pick_buffer(device, max_instances);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("debug.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(DEBUG_BUFFER_SIZE),
+                },
+                count: None,
+            }],
+            label: Some("debug bind group layout"),
+        });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("debug bind group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("debug_vs_main"),
+                // every vertex is generated from the debug buffer and the vertex index
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("debug_fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            buffer,
+            selected: None,
+            render_pipeline,
+            render_bind_group,
+            pick_buffer,
+            max_instances,
+            pick: Pick::Idle,
+        }
+    }
+
+    // the debug buffer starts zeroed, so index 0 would be selected until told otherwise
+    pub fn init(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&NO_SELECTION));
+    }
+
+    // room to copy `max_instances` boids for picking. A pick under way starts over with the new buffer
+    pub fn resize(&mut self, device: &wgpu::Device, max_instances: u32) {
+        self.pick_buffer = create_pick_buffer(device, max_instances);
+        self.max_instances = max_instances;
+        if let Pick::Copied(world_pos) | Pick::Mapping(world_pos, _) = self.pick {
+            self.pick = Pick::Requested(world_pos);
+        }
+    }
+
+    // select the boid closest to `world_pos` on a later frame, or clear the selection if none is close
+    pub fn request_pick(&mut self, world_pos: [f32; 2]) {
+        if matches!(self.pick, Pick::Idle | Pick::Requested(_)) {
+            self.pick = Pick::Requested(world_pos);
+        }
+    }
+
+    // copy the boids that are about to be drawn so a requested pick can search them
+    pub fn encode_pick(&mut self, encoder: &mut wgpu::CommandEncoder, boids: &wgpu::Buffer, num_instances: u32) {
+        if let Pick::Requested(world_pos) = self.pick {
+            let size = num_instances.min(self.max_instances) as u64 * std::mem::size_of::<Boid>() as u64;
+            encoder.copy_buffer_to_buffer(boids, 0, &self.pick_buffer, 0, size);
+            self.pick = Pick::Copied(world_pos);
+        }
+    }
+
+    // must be called after the encoder holding the pick copy has been submitted
+    pub fn map_pick(&mut self) {
+        if let Pick::Copied(world_pos) = self.pick {
+            let (sender, receiver) = mpsc::channel();
+            self.pick_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+            self.pick = Pick::Mapping(world_pos, receiver);
+        }
+    }
+
+    // finish a pick once the copied boids are readable, without blocking the frame
+    pub fn update(&mut self, queue: &wgpu::Queue, num_instances: u32) {
+        let Pick::Mapping(world_pos, receiver) = &self.pick else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+        };
+        let world_pos = *world_pos;
+        self.pick = Pick::Idle;
+        if result.is_err() {
+            log::warn!("Failed to read back boids for picking");
+            return;
+        }
+
+        // with no boids left there's nothing to read, and an empty slice of the buffer isn't allowed
+        let num_instances = num_instances.min(self.max_instances);
+        if num_instances == 0 {
+            self.selected = None;
+        } else {
+            let size = num_instances as u64 * std::mem::size_of::<Boid>() as u64;
+            let data = self.pick_buffer.slice(..size).get_mapped_range();
+            let boids: &[Boid] = bytemuck::cast_slice(&data);
+            self.selected = nearest_boid(boids, world_pos, PICK_RADIUS);
+        }
+        self.pick_buffer.unmap();
+
+        let selected = self.selected.unwrap_or(NO_SELECTION);
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&selected));
+        match self.selected {
+            Some(index) => log::info!("Selected boid {}", index),
+            None => log::info!("Cleared boid selection"),
+        }
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.selected.is_none() {
+            return;
+        }
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.render_bind_group, &[]);
+        render_pass.draw(0..DEBUG_VERTEX_COUNT, 0..1);
+    }
+}
+
+fn create_pick_buffer(device: &wgpu::Device, max_instances: u32) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Pick Buffer"),
+        size: max_instances as u64 * std::mem::size_of::<Boid>() as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn nearest_boid(boids: &[Boid], world_pos: [f32; 2], max_distance: f32) -> Option<u32> {
+    boids.iter()
+        .enumerate()
+        .map(|(i, boid)| {
+            let dx = boid.pos[0] - world_pos[0];
+            let dy = boid.pos[1] - world_pos[1];
+            (i as u32, dx * dx + dy * dy)
+        })
+        .filter(|(_, dist_sq)| *dist_sq <= max_distance * max_distance)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
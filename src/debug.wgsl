@@ -0,0 +1,110 @@
+// Debug overlay shader
+
+#import debug_types.wgsl
+#import math.wgsl
+
+@group(0) @binding(0)
+var<storage, read> debug_info: DebugInfo;
+
+const CIRCLE_SEGMENTS : u32 = 64u;
+const ARROW_VERTICES : u32 = 6u; // shaft + two strokes for the head
+const ARROW_SCALE : f32 = 0.25; // contributions are velocity changes, scale them to be visible
+const ARROW_HEAD : f32 = 0.01;
+const CONE_EDGE_VERTICES : u32 = 8u; // two field of view edges, two blind spot edges
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+// the line list is laid out as: perception arc, separation circle, 3 arrows, vision cone edges, neighbor lines.
+// The perception circle only covers the field of view, so it shows the cone's outline
+@vertex
+fn debug_vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    var pos = debug_info.pos;
+    let circle_vertices = 2u * CIRCLE_SEGMENTS;
+    let arrows_start = 2u * circle_vertices;
+    let edges_start = arrows_start + 3u * ARROW_VERTICES;
+    let neighbors_start = edges_start + CONE_EDGE_VERTICES;
+    let heading = atan2(debug_info.vel.y, debug_info.vel.x);
+
+    if (index < circle_vertices) {
+        pos = arc_vertex(debug_info.perception_radius, heading - debug_info.fov * 0.5, debug_info.fov, index);
+        out.color = vec3<f32>(0.4, 0.7, 1.0); // light blue
+    } else if (index < arrows_start) {
+        pos = arc_vertex(debug_info.separation_radius, 0.0, TAU, index - circle_vertices);
+        out.color = vec3<f32>(1.0, 0.4, 0.2); // orange
+    } else if (index < edges_start) {
+        let arrow = (index - arrows_start) / ARROW_VERTICES;
+        let vertex = (index - arrows_start) % ARROW_VERTICES;
+        if (arrow == 0u) {
+            pos = arrow_vertex(debug_info.separation, vertex);
+            out.color = vec3<f32>(1.0, 0.1, 0.1); // red
+        } else if (arrow == 1u) {
+            pos = arrow_vertex(debug_info.alignment, vertex);
+            out.color = vec3<f32>(0.1, 1.0, 0.1); // green
+        } else {
+            pos = arrow_vertex(debug_info.cohesion, vertex);
+            out.color = vec3<f32>(0.2, 0.4, 1.0); // blue
+        }
+    } else if (index < neighbors_start) {
+        let vertex = index - edges_start;
+        // field of view edges at +-fov/2, blind spot edges at 180 -+ blind_spot/2
+        let is_blind = vertex >= 4u;
+        let side = select(-1.0, 1.0, (vertex / 2u) % 2u == 1u);
+        var angle = heading + side * debug_info.fov * 0.5;
+        var visible = debug_info.fov < TAU;
+        out.color = vec3<f32>(0.4, 0.7, 1.0); // light blue, like the perception arc
+        if (is_blind) {
+            angle = heading + TAU * 0.5 - side * debug_info.blind_spot * 0.5;
+            visible = debug_info.blind_spot > 0.0;
+            out.color = vec3<f32>(0.5, 0.5, 0.5); // grey
+        }
+        // lines of a full view or no blind spot collapse onto the selected boid
+        if (vertex % 2u == 1u && visible) {
+            pos += debug_info.perception_radius * vec2<f32>(cos(angle), sin(angle));
+        }
+    } else {
+        let neighbor = (index - neighbors_start) / 2u;
+        // lines past the recorded neighbors collapse onto the selected boid
+        if (index % 2u == 1u && neighbor < debug_info.neighbor_count) {
+            pos = debug_info.neighbors[neighbor];
+        }
+        out.color = vec3<f32>(0.8, 0.8, 0.8); // light grey
+    }
+    out.clip_position = vec4<f32>(pos, 0.0, 1.0);
+    return out;
+}
+
+// `span` radians of a circle starting at angle `start`, TAU for a full circle
+fn arc_vertex(radius: f32, start: f32, span: f32, index: u32) -> vec2<f32> {
+    // each segment is a pair of vertices, the second one starts the next segment
+    let k = index / 2u + index % 2u;
+    let angle = start + span * f32(k) / f32(CIRCLE_SEGMENTS);
+    return debug_info.pos + radius * vec2<f32>(cos(angle), sin(angle));
+}
+
+fn arrow_vertex(contribution: vec2<f32>, vertex: u32) -> vec2<f32> {
+    let tip = debug_info.pos + contribution * ARROW_SCALE;
+    let len = length(contribution);
+    if (vertex == 0u) {
+        return debug_info.pos;
+    }
+    if (vertex % 2u == 1u || len == 0.0) {
+        return tip;
+    }
+    // head strokes point back from the tip at +-30 degrees
+    let back = -contribution / len * ARROW_HEAD;
+    let side = select(-0.5, 0.5, vertex == 2u);
+    let c = 0.8660254; // cos(30)
+    return tip + vec2<f32>(
+        back.x * c - back.y * side,
+        back.x * side + back.y * c
+    );
+}
+
+@fragment
+fn debug_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
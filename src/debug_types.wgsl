@@ -0,0 +1,18 @@
+// DebugInfo struct that matches the debug buffer in debug.rs, written by compute.wgsl and read by debug.wgsl
+
+const MAX_DEBUG_NEIGHBORS : u32 = 512u;
+
+struct DebugInfo {
+    selected: u32,
+    neighbor_count: u32,
+    perception_radius: f32,
+    separation_radius: f32,
+    pos: vec2<f32>,
+    vel: vec2<f32>,
+    separation: vec2<f32>,
+    alignment: vec2<f32>,
+    cohesion: vec2<f32>,
+    fov: f32,
+    blind_spot: f32,
+    neighbors: array<vec2<f32>, MAX_DEBUG_NEIGHBORS>,
+}
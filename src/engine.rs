@@ -1,15 +1,24 @@
-use log::debug;
-use crate::state::State;
+use crate::{sim::Space, state::State};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{arena::ArenaShape, boid::Boid, capture::write_png, flow::{FLOW_CURL_NOISE, FLOW_NONE, FLOW_WIND}, models::BehaviorModel, params::{INTEGRATOR_NAMES, INTEGRATOR_SEMI_IMPLICIT_EULER, MAX_K_NEIGHBORS, NEIGHBORS_TOPOLOGICAL}, reference, postprocess::PostConfig, record::{Recorder, RecordOptions}, scenario::Scenario, sim::{Simulation, POPULATION}};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
 use winit::{
-    dpi::LogicalSize, event::*, event_loop::EventLoop, keyboard::{KeyCode, PhysicalKey}, window::{Window, WindowBuilder}
+    event::*, event_loop::EventLoop, keyboard::{KeyCode, PhysicalKey}, window::{Window, WindowBuilder}
 };
 
 #[cfg_attr(target_arch="wasm32", wasm_bindgen(start))]
 pub async fn run() {
+    run_with(Space::default(), false).await;
+}
+
+// open the window with the boids in the given space, with `watch_shaders` reading the shader files
+// from the source tree and reloading them when they change
+pub async fn run_with(space: Space, watch_shaders: bool) {
     // replace env_logger:init line with console.log for wasm
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
@@ -43,14 +52,20 @@ pub async fn run() {
             .expect("Couldn't append canvas to document body.");
     }
 
-    let mut state = State::new(&window).await;
+    let mut state = State::new(&window, space).await;
+    #[cfg(not(target_arch = "wasm32"))]
+    if watch_shaders {
+        state.sim.watch_shaders(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src")));
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = watch_shaders;
     let mut surface_configured = false;
     let _ = event_loop.run(move |event, control_flow| {
         match event {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == state.window.id() => if !state.input(event) {
+            } if window_id == state.window.id() && !state.input(event) => {
                 match event {
                     WindowEvent::CloseRequested
                     | WindowEvent::KeyboardInput {
@@ -100,4 +115,315 @@ pub async fn run() {
             _ => {}
         }
     });
-}
\ No newline at end of file
+}
+
+// Options for rendering without a window, parsed from the command line
+#[cfg(not(target_arch = "wasm32"))]
+pub struct HeadlessOptions {
+    pub width: u32,
+    pub height: u32,
+    // simulation steps to run before the image (or first recorded frame) is taken,
+    // defaults to 300 for a single image and 0 for recordings
+    pub steps: Option<u32>,
+    pub population: u32,
+    // where the single image is written when not recording
+    pub output: PathBuf,
+    // record a frame sequence or video instead of a single image
+    pub record: Option<PathBuf>,
+    pub frames: u32,
+    pub fps: u32,
+    pub steps_per_frame: u32,
+    // use a CPU adapter, for machines without a GPU or display server
+    pub software: bool,
+    pub space: Space,
+    // scenario file to start from, the other options are applied on top of it
+    pub scenario: Option<PathBuf>,
+    // use the k nearest neighbors instead of everyone within the perception radius
+    pub k_nearest: Option<u32>,
+    // make the first N boids leaders
+    pub leaders: Option<u32>,
+    // strength of the random steering, and the seed it's drawn from
+    pub wander: Option<f32>,
+    pub seed: Option<u32>,
+    // one of INTEGRATOR_NAMES, with dashes for spaces
+    pub integrator: Option<u32>,
+    // behavior model, by its key
+    pub model: Option<BehaviorModel>,
+    // WGSL file defining extra_force
+    pub extra_force: Option<PathBuf>,
+    // shape of the 2D world, by its name
+    pub arena: Option<ArenaShape>,
+    // directory to read the shader files from instead of the copies built in
+    pub shaders: Option<PathBuf>,
+    // step the CPU reference alongside the kernel and print how far apart the flocks end up
+    pub reference: bool,
+    // "wind", "curl", or a flow field file to load
+    pub flow: Option<String>,
+    // post-processing effects, in order, e.g. "bloom,tonemap=1.2"
+    pub post: PostConfig,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            steps: None,
+            population: POPULATION,
+            output: PathBuf::from("boids.png"),
+            record: None,
+            frames: 600,
+            fps: 60,
+            steps_per_frame: 1,
+            software: false,
+            space: Space::default(),
+            scenario: None,
+            k_nearest: None,
+            leaders: None,
+            wander: None,
+            seed: None,
+            integrator: None,
+            model: None,
+            extra_force: None,
+            arena: None,
+            shaders: None,
+            reference: false,
+            flow: None,
+            post: PostConfig::default(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HeadlessOptions {
+    pub const USAGE: &'static str = "usage: boids_sim --headless [--size WIDTHxHEIGHT] [--steps N] [--population N] [--software] [--3d | --sphere] [--scenario FILE.ron] [--k-nearest K] [--leaders N] [--wander AMOUNT] [--seed N]
+                          [--integrator explicit-euler|semi-implicit-euler|velocity-verlet|rk2] [--model reynolds|vicsek|couzin|cucker-smale|dorsogna] [--extra-force FILE.wgsl] [--shaders DIR] [--reference]
+                          [--arena square|circle|ellipse|polygon] [--flow wind|curl|FILE] [--post EFFECT[=AMOUNT],...]
+                          [--output FILE.png | --record DIR|FILE.y4m [--frames N] [--fps N] [--steps-per-frame N]]
+       boids_sim [--3d | --sphere] [--watch-shaders]";
+
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+            let invalid = || format!("invalid value for {}", arg);
+            match arg.as_str() {
+                "--headless" => {}
+                "--software" => options.software = true,
+                "--3d" => options.space = Space::Volume,
+                "--sphere" => options.space = Space::Sphere,
+                "--scenario" => options.scenario = Some(PathBuf::from(value()?)),
+                "--flow" => options.flow = Some(value()?.clone()),
+                "--k-nearest" => options.k_nearest = Some(value()?.parse().map_err(|_| invalid())?),
+                "--leaders" => options.leaders = Some(value()?.parse().map_err(|_| invalid())?),
+                "--wander" => options.wander = Some(value()?.parse().map_err(|_| invalid())?),
+                "--seed" => options.seed = Some(value()?.parse().map_err(|_| invalid())?),
+                "--integrator" => {
+                    let name = value()?;
+                    let index = INTEGRATOR_NAMES.iter().position(|integrator| integrator.replace(' ', "-").eq_ignore_ascii_case(name)).ok_or_else(invalid)?;
+                    options.integrator = Some(index as u32);
+                }
+                "--model" => {
+                    let key = value()?;
+                    options.model = Some(BehaviorModel::ALL.into_iter().find(|model| model.key() == key).ok_or_else(invalid)?);
+                }
+                "--extra-force" => options.extra_force = Some(PathBuf::from(value()?)),
+                "--arena" => {
+                    let name = value()?;
+                    options.arena = Some(ArenaShape::ALL.into_iter().find(|shape| shape.name() == name).ok_or_else(invalid)?);
+                }
+                "--shaders" => options.shaders = Some(PathBuf::from(value()?)),
+                "--reference" => options.reference = true,
+                // the watcher rebuilds pipelines between frames of a window, a headless run reads its shaders once
+                "--watch-shaders" => return Err("--watch-shaders needs a window, use --shaders DIR to run headless with shaders from disk".to_string()),
+                "--post" => options.post = PostConfig::from_spec(value()?)?,
+                "--size" => (options.width, options.height) = parse_size(value()?)?,
+                "--steps" => options.steps = Some(value()?.parse().map_err(|_| invalid())?),
+                "--population" => options.population = value()?.parse().map_err(|_| invalid())?,
+                "--output" => options.output = PathBuf::from(value()?),
+                "--record" => options.record = Some(PathBuf::from(value()?)),
+                "--frames" => options.frames = value()?.parse().map_err(|_| invalid())?,
+                "--fps" => options.fps = value()?.parse().map_err(|_| invalid())?,
+                "--steps-per-frame" => options.steps_per_frame = value()?.parse().map_err(|_| invalid())?,
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid size {}, expected WIDTHxHEIGHT", size);
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    Ok((width.parse().map_err(|_| invalid())?, height.parse().map_err(|_| invalid())?))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+// every step is submitted on its own, so the uniforms written for it (like the flow field's clock) apply to it
+fn run_steps(sim: &mut Simulation, steps: u32) {
+    for _ in 0..steps {
+        let mut encoder = sim.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Step Encoder"),
+        });
+        sim.encode_step(&mut encoder);
+        sim.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+// run the CPU reference from `start` for as many steps as `sim` took and print how far the boids are apart
+#[cfg(not(target_arch = "wasm32"))]
+fn compare_with_reference(sim: &Simulation, start: Vec<Boid>, steps: u32) -> std::io::Result<()> {
+    // everything the CPU reference leaves out, see reference.rs
+    let unsupported = [
+        (sim.model != BehaviorModel::Reynolds, "the CPU reference only has the Reynolds model"),
+        (sim.extra_force.is_some(), "the CPU reference can't run an extra_force"),
+        (sim.arena.settings.shape != ArenaShape::Square, "the CPU reference only wraps around the square"),
+        (sim.params.wander != 0.0, "the CPU reference doesn't wander"),
+        (!sim.leaders.settings.boids.is_empty(), "the CPU reference has no leaders"),
+        (sim.flow.params.kind != FLOW_NONE, "the CPU reference has no flow fields"),
+        (!sim.forces.sources.is_empty(), "the CPU reference has no force sources"),
+        (!sim.paths.paths.is_empty(), "the CPU reference has no paths"),
+        (!sim.lifecycle.regions.is_empty(), "the CPU reference has no emitters or sinks"),
+    ];
+    if let Some((_, reason)) = unsupported.into_iter().find(|&(used, _)| used) {
+        return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, reason));
+    }
+    let mut boids = start;
+    for _ in 0..steps {
+        boids = reference::step(&boids, &sim.params);
+    }
+    let gpu = sim.read_boids()?;
+    let distances: Vec<f32> = gpu.iter().zip(&boids)
+        .map(|(a, b)| ((a.pos[0] - b.pos[0]).powi(2) + (a.pos[1] - b.pos[1]).powi(2)).sqrt())
+        .collect();
+    let max = distances.iter().fold(0.0f32, |max, &d| max.max(d));
+    let mean = distances.iter().sum::<f32>() / distances.len().max(1) as f32;
+    println!("{} integrator, {} steps: GPU and CPU reference positions differ by {:.3e} on average, {:.3e} at most",
+        INTEGRATOR_NAMES.get(sim.params.integrator as usize).unwrap_or(&INTEGRATOR_NAMES[INTEGRATOR_SEMI_IMPLICIT_EULER as usize]), steps, mean, max);
+    Ok(())
+}
+
+// Run the simulation without a window and either save the final frame as a PNG,
+// or record a fixed number of frames as a PNG sequence or Y4M video
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn run_headless(options: HeadlessOptions) -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let mut sim = Simulation::new_headless(options.software, options.population, options.space).await?;
+    if let Some(dir) = &options.shaders {
+        sim.watch_shaders(dir);
+        if let Some(err) = sim.shader_error.take() {
+            return Err(err.into());
+        }
+    }
+    sim.post_config = options.post.clone();
+    if let Some(path) = &options.scenario {
+        Scenario::load(path)?.apply(&mut sim);
+    }
+    if let Some(k) = options.k_nearest {
+        sim.params.neighbor_mode = NEIGHBORS_TOPOLOGICAL;
+        sim.params.k_neighbors = k.clamp(1, MAX_K_NEIGHBORS);
+    }
+    if let Some(count) = options.leaders {
+        sim.leaders.set_count(count);
+    }
+    if let Some(wander) = options.wander {
+        sim.params.wander = wander;
+    }
+    if let Some(seed) = options.seed {
+        sim.params.seed = seed;
+    }
+    if let Some(integrator) = options.integrator {
+        sim.params.integrator = integrator;
+    }
+    if let Some(model) = options.model {
+        sim.model = model;
+    }
+    if let Some(shape) = options.arena {
+        sim.arena.settings.shape = shape;
+    }
+    if let Some(path) = &options.extra_force {
+        sim.set_extra_force(Some(std::fs::read_to_string(path)?))?;
+    }
+    match options.flow.as_deref() {
+        None => {}
+        Some("wind") => sim.flow.params.kind = FLOW_WIND,
+        Some("curl") => sim.flow.params.kind = FLOW_CURL_NOISE,
+        Some(path) => sim.flow.load(&sim.queue, Path::new(path))?,
+    }
+
+    if let Some(output) = &options.record {
+        run_steps(&mut sim, options.steps.unwrap_or(0));
+        let mut record_options = RecordOptions::new(output.clone(), options.width, options.height);
+        record_options.fps = options.fps;
+        record_options.steps_per_frame = options.steps_per_frame;
+        let mut recorder = Recorder::new(&sim, record_options)?;
+        // the first frame shows the starting state, every following one is `steps_per_frame` later
+        for frame in 0..options.frames {
+            if frame > 0 {
+                run_steps(&mut sim, options.steps_per_frame);
+            }
+            recorder.capture(&mut sim)?;
+        }
+        recorder.finish()?;
+        return Ok(());
+    }
+
+    let steps = options.steps.unwrap_or(300);
+    let start = if options.reference { Some(sim.read_boids()?) } else { None };
+    run_steps(&mut sim, steps);
+    if let Some(start) = start {
+        compare_with_reference(&sim, start, steps)?;
+    }
+    let target = sim.render_offscreen(options.width, options.height)?;
+    let pixels = target.read_rgba(&sim.device, &sim.queue)?;
+    write_png(&options.output, target.width, target.height, &pixels)?;
+    log::info!("Wrote {} after {} steps", options.output.display(), steps);
+    Ok(())
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn sizes_parse_as_width_by_height() {
+        assert_eq!(parse_size("1920x1080"), Ok((1920, 1080)));
+        assert_eq!(parse_size("64x1"), Ok((64, 1)));
+        for malformed in ["1920", "1920x", "x1080", "1920*1080", "-1x10", "1920x1080x2"] {
+            assert_eq!(parse_size(malformed), Err(format!("invalid size {}, expected WIDTHxHEIGHT", malformed)));
+        }
+    }
+
+    #[test]
+    fn options_come_from_the_arguments() {
+        let options = HeadlessOptions::from_args(&args("--headless --size 640x480 --population 300 --software --sphere --model vicsek --arena circle --output out.png")).unwrap();
+        assert_eq!((options.width, options.height), (640, 480));
+        assert_eq!(options.population, 300);
+        assert!(options.software);
+        assert_eq!(options.space, Space::Sphere);
+        assert_eq!(options.model, Some(BehaviorModel::Vicsek));
+        assert_eq!(options.arena, Some(ArenaShape::Circle));
+        assert_eq!(options.output, PathBuf::from("out.png"));
+    }
+
+    #[test]
+    fn bad_arguments_are_reported() {
+        let error = |line| HeadlessOptions::from_args(&args(line)).err();
+        assert_eq!(error("--headless --steps"), Some("missing value for --steps".to_string()));
+        assert_eq!(error("--size"), Some("missing value for --size".to_string()));
+        assert_eq!(error("--size 640by480"), Some("invalid size 640by480, expected WIDTHxHEIGHT".to_string()));
+        assert_eq!(error("--population many"), Some("invalid value for --population".to_string()));
+        assert_eq!(error("--model boids"), Some("invalid value for --model".to_string()));
+        assert_eq!(error("--arena triangle"), Some("invalid value for --arena".to_string()));
+        assert_eq!(error("--fast"), Some("unknown argument --fast".to_string()));
+        assert!(error("--headless --watch-shaders").is_some_and(|err| err.contains("--shaders DIR")));
+    }
+}
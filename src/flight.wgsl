@@ -0,0 +1,74 @@
+// Steering and speed limits and the integrators, imported by compute.wgsl and compute3d.wgsl.
+// The velocity is worked out in this order:
+// 1. the steering of all the rules is cut to `params.max_force`, unless it's 0
+// 2. drag slows the old velocity down, then the steering is added
+// 3. the heading turns towards that velocity by at most `params.max_turn_rate`, unless it's 0
+// 4. the speed is kept between `params.min_speed` and `params.max_speed`
+// The integrator decides where the steering is looked at and how position and velocity advance:
+// explicit Euler moves with the old velocity, semi-implicit Euler with the new one,
+// velocity Verlet averages the steering at the old and the new position,
+// and RK2 steers with what it finds half a step ahead. The neighbors are moved along with their
+// velocity to the time each look is taken at. See reference.rs for the same on the CPU
+
+#import math.wgsl
+#import random.wgsl
+
+// the rules were tuned as the velocity change of one step of 0.03 seconds, this makes them accelerations
+const STEERING_RATE : f32 = 1.0 / 0.03;
+
+// `v` scaled to length 1, or `fallback` when it's too short to have a direction
+fn safe_normalize(v: vec3<f32>, fallback: vec3<f32>) -> vec3<f32> {
+    let len = length(v);
+    if (len < 1.0e-12) {
+        return fallback;
+    }
+    return v / len;
+}
+
+// heading of a boid that has come to a standstill, random but the same every step
+fn rest_heading(i: u32, volume: bool) -> vec3<f32> {
+    let theta = hash01(pcg(i ^ params.seed)) * 2.0 * PI;
+    if (!volume) {
+        return vec3<f32>(cos(theta), sin(theta), 0.0);
+    }
+    let z = hash01(pcg(i ^ params.seed) + 1u) * 2.0 - 1.0;
+    let r = sqrt(1.0 - z * z);
+    return vec3<f32>(r * cos(theta), r * sin(theta), z);
+}
+
+// unit `heading` turned towards unit `desired` by at most `max_angle` radians, in the xy plane unless `volume`
+fn turn_towards(heading: vec3<f32>, desired: vec3<f32>, max_angle: f32, volume: bool) -> vec3<f32> {
+    let c = clamp(dot(heading, desired), -1.0, 1.0);
+    if (acos(c) <= max_angle) {
+        return desired;
+    }
+    // the direction to turn to, any side when the desired heading is straight behind
+    var side = desired - heading * c;
+    if (length(side) < 1.0e-6) {
+        let axis = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(heading.x) > 0.9);
+        side = select(cross(heading, vec3<f32>(0.0, 0.0, 1.0)), cross(heading, axis), volume);
+    }
+    return heading * cos(max_angle) + normalize(side) * sin(max_angle);
+}
+
+// 1. `acc` cut to the strongest acceleration a boid manages
+fn limit_force(acc: vec3<f32>) -> vec3<f32> {
+    if (params.max_force > 0.0 && length(acc) > params.max_force) {
+        return normalize(acc) * params.max_force;
+    }
+    return acc;
+}
+
+// velocity of boid `i` after accelerating by `acc`, already limited, for `dt` seconds
+fn fly(i: u32, vel: vec3<f32>, acc: vec3<f32>, dt: f32, volume: bool) -> vec3<f32> {
+    // 2. linear drag, exact for any time step
+    let desired = vel * exp(-params.drag * dt) + acc * dt;
+    // 3. a boid at a standstill heads straight where it wants to go, or a fixed random way if nowhere
+    let heading = safe_normalize(vel, safe_normalize(desired, rest_heading(i, volume)));
+    // a whole turn when there's no limit, more than any heading is away
+    let max_angle = select(2.0 * PI, params.max_turn_rate * dt, params.max_turn_rate > 0.0);
+    let turned = turn_towards(heading, safe_normalize(desired, heading), max_angle, volume);
+    // 4. never stall and never exceed the top speed
+    let speed = clamp(length(desired), params.min_speed, max(params.min_speed, params.max_speed));
+    return turned * speed;
+}
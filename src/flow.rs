@@ -0,0 +1,248 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::{self, File}, io, path::Path};
+
+use crate::shaders;
+
+pub const FLOW_NONE: u32 = 0;
+// the same velocity everywhere
+pub const FLOW_WIND: u32 = 1;
+// swirling, divergence free noise that changes over time
+pub const FLOW_CURL_NOISE: u32 = 2;
+// a grid of vectors loaded from an image or grid file
+pub const FLOW_GRID: u32 = 3;
+pub const FLOW_NAMES: [&str; 4] = ["none", "wind", "curl noise", "loaded field"];
+
+// loaded fields are resampled to fit in this many cells per side
+pub const MAX_FLOW_GRID_SIDE: u32 = 256;
+// must match ARROW_GRID and ARROW_VERTICES in flow_arrows.wgsl
+const ARROW_VERTEX_COUNT: u32 = 32 * 32 * 6;
+
+// FlowParams struct that matches flow.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FlowParams {
+    pub kind: u32,
+    // how much of the flow velocity is added to a boid's velocity each step
+    pub strength: f32,
+    pub time: f32,
+    pub noise_scale: f32,
+    pub wind: [f32; 3],
+    // how fast the curl noise evolves, relative to simulation time
+    pub time_scale: f32,
+    pub grid_size: [u32; 2],
+    pub _pad: [u32; 2],
+}
+
+impl Default for FlowParams {
+    fn default() -> Self {
+        Self {
+            kind: FLOW_NONE,
+            strength: 0.1,
+            time: 0.0,
+            noise_scale: 2.0,
+            wind: [1.0, 0.0, 0.0],
+            time_scale: 1.0,
+            grid_size: [1, 1],
+            _pad: [0; 2],
+        }
+    }
+}
+
+// Vector field pushing the boids around like moving air, sampled by the compute kernel
+// as an extra force. Also draws itself as a grid of arrows in 2D.
+pub struct FlowField {
+    pub params: FlowParams,
+    pub show_arrows: bool,
+    pub grid_buffer: wgpu::Buffer,
+    pub params_buffer: wgpu::Buffer,
+    arrow_pipeline: wgpu::RenderPipeline,
+    arrow_bind_group: wgpu::BindGroup,
+}
+
+impl FlowField {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let grid_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Flow Grid Buffer"),
+            size: (MAX_FLOW_GRID_SIDE * MAX_FLOW_GRID_SIDE) as u64 * 8,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Flow Params Buffer"),
+            size: std::mem::size_of::<FlowParams>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &Self::layout_entries(wgpu::ShaderStages::VERTEX),
+            label: Some("flow arrows bind group layout"),
+        });
+        let arrow_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("flow arrows bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 6, resource: grid_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 7, resource: params_buffer.as_entire_binding() },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Flow Arrows Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("flow_arrows.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Flow Arrows Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let arrow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Flow Arrows Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("flow_vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("flow_fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            params: FlowParams::default(),
+            show_arrows: false,
+            grid_buffer,
+            params_buffer,
+            arrow_pipeline,
+            arrow_bind_group,
+        }
+    }
+
+    // the grid and params bindings, shared by the compute kernel's layout and the arrows
+    pub fn layout_entries(visibility: wgpu::ShaderStages) -> [wgpu::BindGroupLayoutEntry; 2] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<FlowParams>() as _),
+                },
+                count: None,
+            },
+        ]
+    }
+
+    // move the field's clock along with one simulation step
+    pub fn advance(&mut self, delta_t: f32) {
+        self.params.time += delta_t * self.params.time_scale;
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    // upload a row-major grid of vectors, top row first, and switch to it.
+    // Grids larger than MAX_FLOW_GRID_SIDE are resampled to fit.
+    pub fn set_grid(&mut self, queue: &wgpu::Queue, width: u32, height: u32, vectors: &[[f32; 2]]) {
+        let (grid_width, grid_height) = (width.min(MAX_FLOW_GRID_SIDE), height.min(MAX_FLOW_GRID_SIDE));
+        let mut grid = Vec::with_capacity((grid_width * grid_height) as usize);
+        for y in 0..grid_height {
+            for x in 0..grid_width {
+                let source_x = x * width / grid_width;
+                let source_y = y * height / grid_height;
+                grid.push(vectors[(source_y * width + source_x) as usize]);
+            }
+        }
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::cast_slice(&grid));
+        self.params.grid_size = [grid_width, grid_height];
+        self.params.kind = FLOW_GRID;
+    }
+
+    // load a field from a PNG (red and green map 0..255 to -1..1) or a text grid file
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(&mut self, queue: &wgpu::Queue, path: &Path) -> io::Result<()> {
+        let is_png = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        let (width, height, vectors) = if is_png { read_png_field(path)? } else { read_grid_field(path)? };
+        self.set_grid(queue, width, height, &vectors);
+        Ok(())
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if !self.show_arrows || self.params.kind == FLOW_NONE {
+            return;
+        }
+        render_pass.set_pipeline(&self.arrow_pipeline);
+        render_pass.set_bind_group(0, &self.arrow_bind_group, &[]);
+        render_pass.draw(0..ARROW_VERTEX_COUNT, 0..1);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_png_field(path: &Path) -> io::Result<(u32, u32, Vec<[f32; 2]>)> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    let channels = match info.color_type {
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        color_type => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("flow field images need red and green channels, {} is {:?}", path.display(), color_type),
+            ));
+        }
+    };
+    let vectors = buffer[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| [pixel[0] as f32 / 255.0 * 2.0 - 1.0, pixel[1] as f32 / 255.0 * 2.0 - 1.0])
+        .collect();
+    Ok((info.width, info.height, vectors))
+}
+
+// "WIDTH HEIGHT" followed by WIDTH * HEIGHT "x y" pairs, row by row from the top,
+// all separated by any whitespace
+#[cfg(not(target_arch = "wasm32"))]
+fn read_grid_field(path: &Path) -> io::Result<(u32, u32, Vec<[f32; 2]>)> {
+    let text = fs::read_to_string(path)?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+    let mut numbers = text.split_whitespace();
+    let mut size = || -> io::Result<u32> {
+        numbers.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0).ok_or_else(|| invalid("expected a positive grid size"))
+    };
+    let (width, height) = (size()?, size()?);
+    let values = numbers.map(|n| n.parse::<f32>()).collect::<Result<Vec<_>, _>>().map_err(|_| invalid("invalid number"))?;
+    if values.len() != (width * height * 2) as usize {
+        return Err(invalid(&format!("expected {} vectors, found {} numbers", width * height, values.len())));
+    }
+    Ok((width, height, values.chunks_exact(2).map(|v| [v[0], v[1]]).collect()))
+}
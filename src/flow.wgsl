@@ -0,0 +1,106 @@
+// Flow field sampling, imported by compute.wgsl, compute3d.wgsl and flow_arrows.wgsl
+
+// FlowParams struct that matches flow.rs
+struct FlowParams {
+    kind: u32,
+    strength: f32,
+    // seconds of field time, advanced by delta_t * time_scale every step
+    time: f32,
+    // curl noise features per world unit
+    noise_scale: f32,
+    // velocity of the uniform wind
+    wind: vec3<f32>,
+    time_scale: f32,
+    // size of the loaded grid, row 0 is the top of the world like in an image
+    grid_size: vec2<u32>,
+}
+
+const FLOW_WIND : u32 = 1u;
+const FLOW_CURL_NOISE : u32 = 2u;
+const FLOW_GRID : u32 = 3u;
+
+@group(0) @binding(6)
+var<storage, read> flow_grid: array<vec2<f32>>;
+@group(0) @binding(7)
+var<uniform> flow: FlowParams;
+
+// pseudo random gradient for an integer lattice point, each component in [-1, 1]
+fn lattice_gradient(p: vec3<i32>) -> vec3<f32> {
+    let n = vec3<u32>(p) * vec3<u32>(1597334673u, 3812015801u, 2798796415u);
+    let h = (n.x ^ n.y ^ n.z) * 1597334673u;
+    let v = vec3<u32>(h, h * 16807u, h * 48271u) >> vec3<u32>(8u);
+    return vec3<f32>(v) / 8388607.5 - 1.0;
+}
+
+// smooth gradient noise, roughly in [-1, 1]
+fn gradient_noise(p: vec3<f32>) -> f32 {
+    let cell = vec3<i32>(floor(p));
+    let f = fract(p);
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    var corners: array<f32, 8>;
+    for (var c = 0; c < 8; c++) {
+        let offset = vec3<i32>(c & 1, (c >> 1u) & 1, (c >> 2u) & 1);
+        corners[c] = dot(lattice_gradient(cell + offset), f - vec3<f32>(offset));
+    }
+    let x0 = mix(mix(corners[0], corners[1], u.x), mix(corners[2], corners[3], u.x), u.y);
+    let x1 = mix(mix(corners[4], corners[5], u.x), mix(corners[6], corners[7], u.x), u.y);
+    return mix(x0, x1, u.z);
+}
+
+// Divergence free noise: the curl of a noise potential, so boids swirl around without bunching up.
+// In 2D the potential is one noise value with time as the third axis,
+// in 3D it's three noise values drifting through time.
+fn curl_noise(pos: vec3<f32>, volume: bool) -> vec3<f32> {
+    let p = pos * flow.noise_scale;
+    let t = flow.time;
+    let e = 0.01;
+    if (!volume) {
+        let q = vec3<f32>(p.xy, t);
+        let dy = gradient_noise(q + vec3<f32>(0.0, e, 0.0)) - gradient_noise(q - vec3<f32>(0.0, e, 0.0));
+        let dx = gradient_noise(q + vec3<f32>(e, 0.0, 0.0)) - gradient_noise(q - vec3<f32>(e, 0.0, 0.0));
+        return vec3<f32>(dy, -dx, 0.0) / (2.0 * e);
+    }
+    // one potential component per offset, sampled around p
+    let offsets = array<vec3<f32>, 3>(vec3<f32>(0.0), vec3<f32>(31.4, 17.1, 9.2), vec3<f32>(-12.7, 45.3, 23.9));
+    var grad: array<vec3<f32>, 3>;
+    for (var k = 0; k < 3; k++) {
+        let q = p + offsets[k] + vec3<f32>(t * 0.7, t * 0.5, t * 0.3);
+        grad[k] = vec3<f32>(
+            gradient_noise(q + vec3<f32>(e, 0.0, 0.0)) - gradient_noise(q - vec3<f32>(e, 0.0, 0.0)),
+            gradient_noise(q + vec3<f32>(0.0, e, 0.0)) - gradient_noise(q - vec3<f32>(0.0, e, 0.0)),
+            gradient_noise(q + vec3<f32>(0.0, 0.0, e)) - gradient_noise(q - vec3<f32>(0.0, 0.0, e)),
+        ) / (2.0 * e);
+    }
+    return vec3<f32>(grad[2].y - grad[1].z, grad[0].z - grad[2].x, grad[1].x - grad[0].y);
+}
+
+// bilinear sample of the loaded grid, which covers the [-1, 1] square (the xy plane in 3D)
+fn grid_flow(pos: vec3<f32>) -> vec3<f32> {
+    let size = vec2<f32>(flow.grid_size);
+    let g = clamp(vec2<f32>(pos.x * 0.5 + 0.5, 0.5 - pos.y * 0.5) * size - 0.5, vec2<f32>(0.0), size - 1.0);
+    let c0 = vec2<u32>(floor(g));
+    let c1 = min(c0 + 1u, flow.grid_size - 1u);
+    let f = g - floor(g);
+    let w = flow.grid_size.x;
+    let top = mix(flow_grid[c0.y * w + c0.x], flow_grid[c0.y * w + c1.x], f.x);
+    let bottom = mix(flow_grid[c1.y * w + c0.x], flow_grid[c1.y * w + c1.x], f.x);
+    return vec3<f32>(mix(top, bottom, f.y), 0.0);
+}
+
+// velocity of the air at `pos`, before the strength is applied
+fn flow_at(pos: vec3<f32>, volume: bool) -> vec3<f32> {
+    switch flow.kind {
+        case FLOW_WIND: {
+            return flow.wind;
+        }
+        case FLOW_CURL_NOISE: {
+            return curl_noise(pos, volume);
+        }
+        case FLOW_GRID: {
+            return grid_flow(pos);
+        }
+        default: {
+            return vec3<f32>(0.0);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Arrow grid showing the flow field
+
+#import flow.wgsl
+
+const ARROW_GRID : u32 = 32u;
+const ARROW_VERTICES : u32 = 6u; // shaft + two strokes for the head
+// flow velocities are around 1, scale them to a bit less than the grid spacing
+const ARROW_SCALE : f32 = 0.04;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn flow_vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let arrow = index / ARROW_VERTICES;
+    let vertex = index % ARROW_VERTICES;
+    let cell = vec2<f32>(f32(arrow % ARROW_GRID), f32(arrow / ARROW_GRID));
+    let center = (cell + 0.5) / f32(ARROW_GRID) * 2.0 - 1.0;
+    let v = flow_at(vec3<f32>(center, 0.0), false).xy * ARROW_SCALE;
+    let tip = center + v;
+
+    var pos = center;
+    if (vertex % 2u == 1u) {
+        pos = tip;
+    } else if (vertex > 0u) {
+        // head strokes point back from the tip at +-30 degrees, a third of the arrow long
+        let back = -v / 3.0;
+        let side = select(-0.5, 0.5, vertex == 2u);
+        let c = 0.8660254; // cos(30)
+        pos = tip + vec2<f32>(back.x * c - back.y * side, back.x * side + back.y * c);
+    }
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(pos, 0.0, 1.0);
+    out.color = vec3<f32>(0.4, 0.9, 0.8); // teal
+    return out;
+}
+
+@fragment
+fn flow_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
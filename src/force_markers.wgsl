@@ -0,0 +1,56 @@
+// Circles showing where the force sources are and how far they reach
+
+#import force_types.wgsl
+#import math.wgsl
+
+@group(0) @binding(0)
+var<storage, read> forces: ForceList;
+
+const CIRCLE_SEGMENTS : u32 = 48u;
+// the radius circle, then a cross on the center
+const MARKER_VERTICES : u32 = 2u * CIRCLE_SEGMENTS + 4u;
+const CROSS_SIZE : f32 = 0.015;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn marker_vs_main(@builtin(vertex_index) index: u32, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let force = forces.items[instance];
+    let circle_vertices = 2u * CIRCLE_SEGMENTS;
+    var offset: vec2<f32>;
+    if (index < circle_vertices) {
+        let segment = index / 2u + index % 2u;
+        let angle = f32(segment) / f32(CIRCLE_SEGMENTS) * TAU;
+        offset = vec2<f32>(cos(angle), sin(angle)) * force.radius;
+    } else {
+        let vertex = index - circle_vertices;
+        let side = select(-CROSS_SIZE, CROSS_SIZE, vertex % 2u == 1u);
+        offset = select(vec2<f32>(0.0, side), vec2<f32>(side, 0.0), vertex < 2u);
+    }
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(force.pos.xy + offset, 0.0, 1.0);
+    switch force.kind {
+        case 1u: {
+            out.color = vec3<f32>(1.0, 0.35, 0.3); // repellers red
+        }
+        case 2u: {
+            out.color = vec3<f32>(0.5, 0.6, 1.0); // vortices blue
+        }
+        default: {
+            out.color = vec3<f32>(0.4, 1.0, 0.5); // attractors green
+        }
+    }
+    if (force.selected != 0u) {
+        out.color = vec3<f32>(1.0, 0.95, 0.4); // yellow
+    }
+    return out;
+}
+
+@fragment
+fn marker_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
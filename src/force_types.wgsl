@@ -0,0 +1,18 @@
+// Force struct that matches forces.rs, read by forces.wgsl and force_markers.wgsl
+
+struct Force {
+    pos: vec3<f32>,
+    radius: f32,
+    strength: f32,
+    kind: u32,
+    falloff: u32,
+    selected: u32,
+}
+
+struct ForceList {
+    count: u32,
+    items: array<Force>,
+}
+
+const FORCE_REPELLER : u32 = 1u;
+const FORCE_VORTEX : u32 = 2u;
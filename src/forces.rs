@@ -0,0 +1,301 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{params::FALLOFF_LINEAR, shaders};
+
+// the force buffer has room for this many sources
+pub const MAX_FORCES: usize = 64;
+// must match MARKER_VERTICES in force_markers.wgsl
+const MARKER_VERTEX_COUNT: u32 = 2 * 48 + 4;
+// bytes before the sources in the force buffer, the count padded to 16
+const HEADER_SIZE: u64 = 16;
+
+// What a force source does to the boids around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ForceKind {
+    // pulls boids towards it
+    #[default]
+    Attractor,
+    // pushes boids away
+    Repeller,
+    // swirls boids around it, counterclockwise for a positive strength
+    Vortex,
+}
+
+impl ForceKind {
+    pub const ALL: [ForceKind; 3] = [ForceKind::Attractor, ForceKind::Repeller, ForceKind::Vortex];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ForceKind::Attractor => "attractor",
+            ForceKind::Repeller => "repeller",
+            ForceKind::Vortex => "vortex",
+        }
+    }
+}
+
+// A placeable point force, as edited in the panel and saved in scenario files.
+// Sources can move in a circle around `position` and pulse in strength over time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForceSource {
+    pub kind: ForceKind,
+    // the source's center, or the center of its orbit. z is only used in 3D
+    pub position: [f32; 3],
+    // boids further away than this aren't affected
+    pub radius: f32,
+    // velocity added per step to a boid right at the source
+    pub strength: f32,
+    // how the strength fades towards the radius, one of the FALLOFF_* constants
+    pub falloff: u32,
+    pub orbit_radius: f32,
+    // radians per second of simulation time
+    pub orbit_speed: f32,
+    // how much the strength swings around its value, 1 goes from 0 to twice the strength
+    pub pulse: f32,
+    // radians per second of simulation time
+    pub pulse_speed: f32,
+}
+
+impl Default for ForceSource {
+    fn default() -> Self {
+        Self {
+            kind: ForceKind::Attractor,
+            position: [0.0; 3],
+            radius: 0.3,
+            strength: 0.3,
+            falloff: FALLOFF_LINEAR,
+            orbit_radius: 0.0,
+            orbit_speed: 0.0,
+            pulse: 0.0,
+            pulse_speed: 0.0,
+        }
+    }
+}
+
+impl ForceSource {
+    // where the source is at `time`
+    pub fn position_at(&self, time: f32) -> [f32; 3] {
+        let angle = time * self.orbit_speed;
+        [
+            self.position[0] + self.orbit_radius * angle.cos(),
+            self.position[1] + self.orbit_radius * angle.sin(),
+            self.position[2],
+        ]
+    }
+
+    fn gpu(&self, time: f32, selected: bool) -> GpuForce {
+        GpuForce {
+            position: self.position_at(time),
+            radius: self.radius,
+            strength: self.strength * (1.0 + self.pulse * (time * self.pulse_speed).sin()),
+            kind: self.kind as u32,
+            falloff: self.falloff,
+            selected: selected as u32,
+        }
+    }
+}
+
+// Force struct that matches forces.wgsl and force_markers.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuForce {
+    position: [f32; 3],
+    radius: f32,
+    strength: f32,
+    kind: u32,
+    falloff: u32,
+    // only used to highlight the marker
+    selected: u32,
+}
+
+// Attractors, repellers and vortices placed in the world. They're evaluated on the CPU
+// at the current time every step and uploaded for the compute kernel, and drawn as
+// circles showing their radius in 2D.
+pub struct Forces {
+    pub sources: Vec<ForceSource>,
+    // seconds of simulation time, drives the animation
+    pub time: f32,
+    // source edited in the panel and dragged with the mouse
+    pub selected: Option<usize>,
+    // the mouse places and drags sources instead of selecting boids
+    pub editing: bool,
+    // kind of source a click on empty space places
+    pub place_kind: ForceKind,
+    pub buffer: wgpu::Buffer,
+    marker_pipeline: wgpu::RenderPipeline,
+    marker_bind_group: wgpu::BindGroup,
+}
+
+impl Forces {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Force Buffer"),
+            size: HEADER_SIZE + (MAX_FORCES * std::mem::size_of::<GpuForce>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut marker_entry = Self::layout_entry(wgpu::ShaderStages::VERTEX);
+        marker_entry.binding = 0;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[marker_entry],
+            label: Some("force markers bind group layout"),
+        });
+        let marker_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("force markers bind group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Force Markers Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("force_markers.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Force Markers Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let marker_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Force Markers Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("marker_vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("marker_fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            sources: Vec::new(),
+            time: 0.0,
+            selected: None,
+            editing: false,
+            place_kind: ForceKind::Attractor,
+            buffer,
+            marker_pipeline,
+            marker_bind_group,
+        }
+    }
+
+    // the force buffer binding of the compute kernel's layout
+    pub fn layout_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: 8,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    pub fn advance(&mut self, delta_t: f32) {
+        self.time += delta_t;
+    }
+
+    // upload the sources as they are at the current time
+    pub fn write(&self, queue: &wgpu::Queue) {
+        let forces: Vec<GpuForce> = self.sources.iter().take(MAX_FORCES).enumerate()
+            .map(|(i, source)| source.gpu(self.time, self.selected == Some(i)))
+            .collect();
+        let header = [forces.len() as u32, 0, 0, 0];
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&header));
+        if !forces.is_empty() {
+            queue.write_buffer(&self.buffer, HEADER_SIZE, bytemuck::cast_slice(&forces));
+        }
+    }
+
+    // add a source of the kind being placed at `position` and select it.
+    // Returns false when the buffer is already full
+    pub fn place(&mut self, position: [f32; 2]) -> bool {
+        if self.sources.len() >= MAX_FORCES {
+            return false;
+        }
+        self.sources.push(ForceSource {
+            kind: self.place_kind,
+            position: [position[0], position[1], 0.0],
+            ..Default::default()
+        });
+        self.selected = Some(self.sources.len() - 1);
+        true
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.sources.remove(index);
+        self.selected = match self.selected {
+            Some(selected) if selected == index => None,
+            Some(selected) if selected > index => Some(selected - 1),
+            selected => selected,
+        };
+    }
+
+    // the source whose current center is closest to `position`, if any is within `max_distance`
+    pub fn pick(&self, position: [f32; 2], max_distance: f32) -> Option<usize> {
+        self.sources.iter().enumerate()
+            .map(|(i, source)| {
+                let center = source.position_at(self.time);
+                (i, (center[0] - position[0]).hypot(center[1] - position[1]))
+            })
+            .filter(|&(_, distance)| distance <= max_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    // move the selected source, orbit and all, by `offset`
+    pub fn drag_selected(&mut self, offset: [f32; 2]) {
+        if let Some(source) = self.selected.and_then(|i| self.sources.get_mut(i)) {
+            source.position[0] += offset[0];
+            source.position[1] += offset[1];
+        }
+    }
+
+    // replace every source, e.g. with the ones from a scenario, restarting the animation
+    pub fn set_sources(&mut self, mut sources: Vec<ForceSource>) {
+        sources.truncate(MAX_FORCES);
+        self.sources = sources;
+        self.selected = None;
+        self.time = 0.0;
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.sources.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.marker_pipeline);
+        render_pass.set_bind_group(0, &self.marker_bind_group, &[]);
+        render_pass.draw(0..MARKER_VERTEX_COUNT, 0..self.sources.len().min(MAX_FORCES) as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::{assert_layout, offsets};
+
+    #[test]
+    fn force_matches_the_shader() {
+        let fields = offsets!(GpuForce { position as pos, radius, strength, kind, falloff, selected });
+        assert_layout("force_types.wgsl", "Force", fields, Some(std::mem::size_of::<GpuForce>()));
+    }
+}
@@ -0,0 +1,37 @@
+// Force sources, imported by compute.wgsl and compute3d.wgsl.
+// Uses `falloff` from the kernel importing it
+
+#import force_types.wgsl
+
+@group(0) @binding(8)
+var<storage, read> forces: ForceList;
+
+// velocity change from every source at `pos`. In 3D vortices swirl around the z axis
+fn force_at(pos: vec3<f32>) -> vec3<f32> {
+    var total = vec3<f32>(0.0);
+    for (var f = 0u; f < forces.count; f++) {
+        let force = forces.items[f];
+        let offset = pos - force.pos;
+        let dist = length(offset);
+        let weight = falloff(force.falloff, dist, force.radius) * force.strength;
+        if (weight == 0.0 || dist == 0.0) {
+            continue;
+        }
+        switch force.kind {
+            case FORCE_REPELLER: {
+                total += offset / dist * weight;
+            }
+            case FORCE_VORTEX: {
+                let around = vec3<f32>(-offset.y, offset.x, 0.0);
+                let around_len = length(around);
+                if (around_len > 0.0) {
+                    total += around / around_len * weight;
+                }
+            }
+            default: {
+                total -= offset / dist * weight;
+            }
+        }
+    }
+    return total;
+}
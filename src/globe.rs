@@ -0,0 +1,98 @@
+use wgpu::util::DeviceExt;
+
+use crate::{postprocess::{DEPTH_FORMAT, HDR_FORMAT}, shaders};
+
+// rings from pole to pole and segments around the globe mesh
+const RINGS: u32 = 48;
+const SEGMENTS: u32 = 96;
+
+// The planet the boids of the sphere mode fly over, a shaded unit sphere with lines of latitude and longitude.
+// It's drawn into the depth buffer before the boids, so the ones on the far side are hidden
+pub struct Globe {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    num_vertices: u32,
+}
+
+impl Globe {
+    pub fn new(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Globe Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("globe.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Globe Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Globe Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("globe_vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("globe_fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let vertices = sphere_vertices();
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Globe Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        Self { pipeline, vertex_buffer, num_vertices: vertices.len() as u32 }
+    }
+
+    // draw into a pass with a depth buffer and the camera bind group set at 0
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
+
+// triangles covering the unit sphere with y up, counterclockwise seen from outside
+fn sphere_vertices() -> Vec<[f32; 3]> {
+    let point = |ring: u32, segment: u32| {
+        let polar = ring as f32 / RINGS as f32 * std::f32::consts::PI;
+        let azimuth = segment as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+        [polar.sin() * azimuth.cos(), polar.cos(), polar.sin() * azimuth.sin()]
+    };
+    let mut vertices = Vec::with_capacity((RINGS * SEGMENTS * 6) as usize);
+    for ring in 0..RINGS {
+        for segment in 0..SEGMENTS {
+            let [a, b, c, d] = [point(ring, segment), point(ring + 1, segment), point(ring + 1, segment + 1), point(ring, segment + 1)];
+            vertices.extend([a, c, b, a, d, c]);
+        }
+    }
+    vertices
+}
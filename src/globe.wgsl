@@ -0,0 +1,39 @@
+// Shaders for the globe of the sphere mode: a lit unit sphere with lines of latitude and longitude
+
+#import camera_types.wgsl
+#import math.wgsl
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// a little under the boids, whose cones are centered on the surface
+const RADIUS : f32 = 0.99;
+// a line of latitude and of longitude every 15 degrees
+const LINE_SPACING : f32 = PI / 12.0;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+};
+
+@vertex
+fn globe_vs_main(@location(0) position: vec3<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.normal = position;
+    out.clip_position = camera.view_proj * vec4<f32>(position * RADIUS, 1.0);
+    return out;
+}
+
+@fragment
+fn globe_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(in.normal);
+    // latitude and longitude in line spacings, and how many pixels away the nearest line is
+    let coords = vec2<f32>(asin(clamp(normal.y, -1.0, 1.0)), atan2(normal.z, normal.x)) / LINE_SPACING;
+    let pixels = abs(fract(coords + 0.5) - 0.5) / fwidth(coords);
+    let line = 1.0 - clamp(min(pixels.x, pixels.y), 0.0, 1.0);
+    let ocean = vec3<f32>(0.05, 0.12, 0.3);
+    let grid = vec3<f32>(0.3, 0.45, 0.7);
+    let diffuse = max(dot(normal, normalize(camera.light_dir.xyz)), 0.0);
+    let ambient = 0.3;
+    return vec4<f32>(mix(ocean, grid, line * 0.8) * (ambient + diffuse), 1.0);
+}
@@ -0,0 +1,190 @@
+use crate::{population::Population, shaders, sim::Space};
+
+// cells per side, about one cell per perception radius at the default population
+pub const GRID_DIM_2D : u32 = 128;
+pub const GRID_DIM_3D : u32 = 32;
+
+// GridParams struct that matches grid.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct GridParams {
+    pub(crate) dims: [u32; 3],
+    pub(crate) stride: u32,
+    pub(crate) _pad0: u32,
+    pub(crate) num_cells: u32,
+    pub(crate) _pad: [u32; 2],
+}
+
+// Uniform grid the topological neighbor search walks outward through, instead of looking at every boid.
+// It's rebuilt from the latest boids at the start of every step.
+pub struct NeighborGrid {
+    // where each cell's boids start in `sorted`, read by the compute kernel
+    pub cell_start: wgpu::Buffer,
+    // boid indices ordered by cell
+    pub sorted: wgpu::Buffer,
+    cell_counts: wgpu::Buffer,
+    // cell and position in it of every boid, only used while building
+    boid_cell: wgpu::Buffer,
+    boid_offset: wgpu::Buffer,
+    params: GridParams,
+    params_buffer: wgpu::Buffer,
+    clear_pipeline: wgpu::ComputePipeline,
+    count_pipeline: wgpu::ComputePipeline,
+    scan_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // one per instance buffer, like the compute bind groups
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl NeighborGrid {
+    pub fn new(device: &wgpu::Device, space: Space, instance_buffers: &[wgpu::Buffer], population: &Population, max_instances: u32) -> Self {
+        let dims = match space {
+            Space::Plane => [GRID_DIM_2D, GRID_DIM_2D, 1],
+            Space::Volume | Space::Sphere => [GRID_DIM_3D; 3],
+        };
+        let num_cells = dims[0] * dims[1] * dims[2];
+        let params = GridParams {
+            dims,
+            stride: (space.boid_size() / 16) as u32,
+            _pad0: 0,
+            num_cells,
+            _pad: [0; 2],
+        };
+
+        let cell_counts = storage_buffer(device, "Grid Cell Counts", num_cells);
+        let cell_start = storage_buffer(device, "Grid Cell Start", num_cells + 1);
+        let [boid_cell, boid_offset, sorted] = boid_buffers(device, max_instances);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Params Buffer"),
+            size: std::mem::size_of::<GridParams>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                storage_entry(0, true),
+                storage_entry(1, false),
+                storage_entry(2, false),
+                storage_entry(3, false),
+                storage_entry(4, false),
+                storage_entry(5, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                Population::layout_entry(wgpu::ShaderStages::COMPUTE),
+            ],
+            label: Some("grid bind group layout"),
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Grid Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("grid.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("grid"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let mut grid = Self {
+            cell_start,
+            sorted,
+            cell_counts,
+            boid_cell,
+            boid_offset,
+            params,
+            params_buffer,
+            clear_pipeline: pipeline("clear"),
+            count_pipeline: pipeline("count"),
+            scan_pipeline: pipeline("scan"),
+            scatter_pipeline: pipeline("scatter"),
+            bind_group_layout,
+            bind_groups: Vec::new(),
+        };
+        grid.create_bind_groups(device, instance_buffers, population);
+        grid
+    }
+
+    fn create_bind_groups(&mut self, device: &wgpu::Device, instance_buffers: &[wgpu::Buffer], population: &Population) {
+        self.bind_groups = instance_buffers.iter().enumerate().map(|(i, boids)| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("grid bind group {}", i)),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: boids.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: self.cell_counts.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: self.cell_start.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 3, resource: self.boid_cell.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 4, resource: self.boid_offset.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 5, resource: self.sorted.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 6, resource: self.params_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 12, resource: population.state_buffer.as_entire_binding() },
+                ],
+            })
+        }).collect();
+    }
+
+    // per-boid buffers for `max_instances` boids, bound to new instance buffers
+    pub fn resize(&mut self, device: &wgpu::Device, instance_buffers: &[wgpu::Buffer], population: &Population, max_instances: u32) {
+        [self.boid_cell, self.boid_offset, self.sorted] = boid_buffers(device, max_instances);
+        self.create_bind_groups(device, instance_buffers, population);
+    }
+
+    // sort the boids in instance buffer `input` into the grid, before the kernel that reads it
+    pub fn encode_build(&mut self, queue: &wgpu::Queue, compute_pass: &mut wgpu::ComputePass, input: usize, population: &Population) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&self.params));
+        compute_pass.set_bind_group(0, &self.bind_groups[input], &[]);
+        compute_pass.set_pipeline(&self.clear_pipeline);
+        compute_pass.dispatch_workgroups(self.params.num_cells.div_ceil(64), 1, 1);
+        compute_pass.set_pipeline(&self.count_pipeline);
+        population.dispatch(compute_pass);
+        compute_pass.set_pipeline(&self.scan_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        compute_pass.set_pipeline(&self.scatter_pipeline);
+        population.dispatch(compute_pass);
+    }
+}
+
+fn storage_buffer(device: &wgpu::Device, label: &str, entries: u32) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: entries as u64 * 4,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    })
+}
+
+// cell, offset in the cell and sorted index buffers, one entry per boid
+fn boid_buffers(device: &wgpu::Device, max_instances: u32) -> [wgpu::Buffer; 3] {
+    [
+        storage_buffer(device, "Grid Boid Cell", max_instances),
+        storage_buffer(device, "Grid Boid Offset", max_instances),
+        storage_buffer(device, "Grid Sorted Boids", max_instances),
+    ]
+}
@@ -0,0 +1,112 @@
+// Uniform grid over the [-1, 1] world, rebuilt every step with a counting sort:
+// clear the counts, count the boids per cell, turn the counts into start offsets with a prefix sum,
+// then scatter the boid indices so each cell's boids are contiguous in `sorted`.
+// Dead boids are left out of the grid
+
+#import alive.wgsl
+
+// GridParams struct that matches grid.rs
+struct GridParams {
+    // cells along x, y and z, z is 1 for the 2D mode
+    dims: vec3<u32>,
+    // boids are read as vec4s, 2D boids take one (pos, vel) and 3D boids two (pos + pad, vel + pad)
+    stride: u32,
+    _pad0: u32,
+    num_cells: u32,
+    _pad: vec2<u32>,
+}
+
+@group(0) @binding(0)
+var<storage, read> boids: array<vec4<f32>>;
+@group(0) @binding(1)
+var<storage, read_write> cell_counts: array<atomic<u32>>;
+// num_cells + 1 entries, the last one is the total so a cell's range is always start[c]..start[c + 1]
+@group(0) @binding(2)
+var<storage, read_write> cell_start: array<u32>;
+@group(0) @binding(3)
+var<storage, read_write> boid_cell: array<u32>;
+// position of each boid among the boids of its cell
+@group(0) @binding(4)
+var<storage, read_write> boid_offset: array<u32>;
+@group(0) @binding(5)
+var<storage, read_write> sorted: array<u32>;
+@group(0) @binding(6)
+var<uniform> grid: GridParams;
+
+const SCAN_THREADS : u32 = 256u;
+
+fn cell_of(pos: vec3<f32>) -> u32 {
+    let dims = vec3<f32>(grid.dims);
+    let cell = vec3<u32>(clamp((pos * 0.5 + 0.5) * dims, vec3<f32>(0.0), dims - 1.0));
+    return (cell.z * grid.dims.y + cell.y) * grid.dims.x + cell.x;
+}
+
+@compute @workgroup_size(64)
+fn clear(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x < grid.num_cells) {
+        atomicStore(&cell_counts[id.x], 0u);
+    }
+}
+
+@compute @workgroup_size(64)
+fn count(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= population.slots || !is_alive(i)) {
+        return;
+    }
+    var pos = boids[i * grid.stride].xyz;
+    if (grid.dims.z == 1u) {
+        pos.z = 0.0;
+    }
+    let cell = cell_of(pos);
+    boid_cell[i] = cell;
+    boid_offset[i] = atomicAdd(&cell_counts[cell], 1u);
+}
+
+var<workgroup> chunk_sums: array<u32, SCAN_THREADS>;
+
+// exclusive prefix sum of the counts, in a single workgroup: every thread sums a chunk of cells,
+// the chunk sums are scanned in shared memory, then each thread writes out its chunk
+@compute @workgroup_size(256)
+fn scan(@builtin(local_invocation_id) local_id: vec3<u32>) {
+    let t = local_id.x;
+    let chunk = (grid.num_cells + SCAN_THREADS - 1u) / SCAN_THREADS;
+    let first = t * chunk;
+    let last = min(first + chunk, grid.num_cells);
+
+    var sum = 0u;
+    for (var c = first; c < last; c++) {
+        sum += atomicLoad(&cell_counts[c]);
+    }
+    chunk_sums[t] = sum;
+    workgroupBarrier();
+
+    // Hillis-Steele inclusive scan over the chunk sums
+    for (var offset = 1u; offset < SCAN_THREADS; offset *= 2u) {
+        var value = chunk_sums[t];
+        if (t >= offset) {
+            value += chunk_sums[t - offset];
+        }
+        workgroupBarrier();
+        chunk_sums[t] = value;
+        workgroupBarrier();
+    }
+
+    var start = chunk_sums[t] - sum;
+    for (var c = first; c < last; c++) {
+        cell_start[c] = start;
+        start += atomicLoad(&cell_counts[c]);
+    }
+    if (t == SCAN_THREADS - 1u) {
+        cell_start[grid.num_cells] = chunk_sums[t];
+    }
+}
+
+@compute @workgroup_size(64)
+fn scatter(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= population.slots || !is_alive(i)) {
+        return;
+    }
+    sorted[cell_start[boid_cell[i]] + boid_offset[i]] = i;
+}
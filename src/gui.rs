@@ -0,0 +1,634 @@
+use std::time::Instant;
+
+use winit::{event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent}, keyboard::{Key, NamedKey}};
+
+use crate::{arena::{Arena, ArenaShape}, flow::{FlowField, FLOW_CURL_NOISE, FLOW_NAMES, FLOW_WIND}, forces::{ForceKind, Forces}, leaders::{LeaderMode, Leaders}, models::BehaviorModel, params::{SimParams, FALLOFF_NAMES, INTEGRATOR_NAMES, MAX_K_NEIGHBORS, NEIGHBORS_METRIC, NEIGHBORS_TOPOLOGICAL}, paths::Paths, population::{Population, Region, RegionKind, SpawnDistribution, MAX_REGIONS}, postprocess::{PostConfig, PostEffect}, sim::{Simulation, Space}};
+
+// Read-only numbers shown at the top of the panel
+pub struct PanelStats {
+    pub fps: f32,
+    pub frame_time_ms: f32,
+    // boids alive, and slots in use including the dead ones waiting to be reused
+    pub population: u32,
+    pub slots: u32,
+    pub capacity: u32,
+    // frames written so far while a recording is running
+    pub recorded_frames: Option<u32>,
+}
+
+// Buttons pressed this frame, applied by the state once the frame is submitted
+#[derive(Default)]
+pub struct PanelActions {
+    pub reset: bool,
+    pub spawn: bool,
+    pub save_scenario: bool,
+    // new number of boids to resize to
+    pub set_population: Option<u32>,
+    // rebuild the kernels without the user's extra_force
+    pub clear_extra_force: bool,
+}
+
+// Immediate-mode overlay drawn with egui on the simulation's own device.
+// winit events are translated to egui input by hand, since egui-winit targets a newer winit.
+pub struct Gui {
+    ctx: egui::Context,
+    renderer: egui_wgpu::Renderer,
+    raw_input: egui::RawInput,
+    pointer_pos: egui::Pos2,
+    modifiers: egui::Modifiers,
+    start_time: Instant,
+}
+
+impl Gui {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        Self {
+            ctx: egui::Context::default(),
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1, false),
+            raw_input: egui::RawInput::default(),
+            pointer_pos: egui::Pos2::ZERO,
+            modifiers: egui::Modifiers::default(),
+            start_time: Instant::now(),
+        }
+    }
+
+    // feed a window event to egui, returns true if the panel wants it for itself
+    pub fn handle_event(&mut self, event: &WindowEvent, scale_factor: f64) -> bool {
+        let pixels_per_point = scale_factor as f32;
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_pos = egui::pos2(position.x as f32 / pixels_per_point, position.y as f32 / pixels_per_point);
+                self.raw_input.events.push(egui::Event::PointerMoved(self.pointer_pos));
+                self.ctx.is_using_pointer()
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.raw_input.events.push(egui::Event::PointerGone);
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    _ => return false,
+                };
+                self.raw_input.events.push(egui::Event::PointerButton {
+                    pos: self.pointer_pos,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                });
+                self.ctx.wants_pointer_input() || self.ctx.is_pointer_over_area()
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (egui::MouseWheelUnit::Line, egui::vec2(*x, *y)),
+                    MouseScrollDelta::PixelDelta(pos) => (
+                        egui::MouseWheelUnit::Point,
+                        egui::vec2(pos.x as f32, pos.y as f32) / pixels_per_point,
+                    ),
+                };
+                self.raw_input.events.push(egui::Event::MouseWheel { unit, delta, modifiers: self.modifiers });
+                self.ctx.is_pointer_over_area()
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                self.modifiers = egui::Modifiers {
+                    alt: state.alt_key(),
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    mac_cmd: false,
+                    command: state.control_key() || state.super_key(),
+                };
+                self.raw_input.modifiers = self.modifiers;
+                false
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == ElementState::Pressed;
+                if let Some(key) = translate_key(&event.logical_key) {
+                    self.raw_input.events.push(egui::Event::Key {
+                        key,
+                        physical_key: None,
+                        pressed,
+                        repeat: event.repeat,
+                        modifiers: self.modifiers,
+                    });
+                }
+                if let Some(text) = &event.text
+                    && pressed
+                    && !text.chars().any(char::is_control)
+                {
+                    self.raw_input.events.push(egui::Event::Text(text.to_string()));
+                }
+                self.ctx.wants_keyboard_input()
+            }
+            _ => false,
+        }
+    }
+
+    // build the panel for this frame, editing the simulation's settings directly,
+    // and record its own render pass on top of `view`
+    pub fn draw(
+        &mut self,
+        sim: &mut Simulation,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: [u32; 2],
+        scale_factor: f64,
+        stats: &PanelStats,
+    ) -> PanelActions {
+        let pixels_per_point = scale_factor as f32;
+        let mut raw_input = std::mem::take(&mut self.raw_input);
+        raw_input.modifiers = self.modifiers;
+        raw_input.time = Some(self.start_time.elapsed().as_secs_f64());
+        raw_input.screen_rect = Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(size[0] as f32, size[1] as f32) / pixels_per_point,
+        ));
+        raw_input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(pixels_per_point);
+
+        let mut actions = PanelActions::default();
+        let output = self.ctx.run(raw_input, |ctx| panel(ctx, stats, sim, &mut actions));
+        let (device, queue) = (&sim.device, &sim.queue);
+        let paint_jobs = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        let screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: size,
+            pixels_per_point: output.pixels_per_point,
+        };
+
+        for (id, image_delta) in &output.textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, image_delta);
+        }
+        // only paint callbacks produce extra command buffers, they have to run before the gui pass
+        let callback_buffers = self.renderer.update_buffers(device, queue, encoder, &paint_jobs, &screen_descriptor);
+        queue.submit(callback_buffers);
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Gui Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // draw over the boids
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            }).forget_lifetime();
+            self.renderer.render(&mut render_pass, &paint_jobs, &screen_descriptor);
+        }
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+        actions
+    }
+}
+
+fn panel(ctx: &egui::Context, stats: &PanelStats, sim: &mut Simulation, actions: &mut PanelActions) {
+    let max_population = sim.max_population();
+    let params = &mut sim.params;
+    let model = &mut sim.model;
+    egui::Window::new("Boids")
+        .default_pos([10.0, 10.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!("FPS: {:.1}", stats.fps));
+            ui.label(format!("Frame time: {:.2} ms", stats.frame_time_ms));
+            ui.label(format!("Population: {} / {} ({} slots in use)", stats.population, stats.capacity, stats.slots));
+            if let Some(frames) = stats.recorded_frames {
+                ui.colored_label(egui::Color32::RED, format!("Recording: {} frames", frames));
+            }
+            // a snippet or reloaded shader that didn't compile, the previous kernels are still running
+            if let Some(error) = &sim.snippet_error {
+                ui.colored_label(egui::Color32::RED, "extra_force error, the previous kernels are still in use:");
+                ui.label(egui::RichText::new(error).monospace().color(egui::Color32::RED));
+            }
+            if let Some(error) = &sim.shader_error {
+                ui.colored_label(egui::Color32::RED, "Shader error, the previous pipeline is still in use:");
+                ui.label(egui::RichText::new(error).monospace().color(egui::Color32::RED));
+            }
+            ui.separator();
+
+            egui::ComboBox::from_label("model")
+                .selected_text(model.name())
+                .show_ui(ui, |ui| {
+                    for option in BehaviorModel::ALL {
+                        ui.selectable_value(model, option, option.name());
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.label("neighbors:");
+                ui.radio_value(&mut params.neighbor_mode, NEIGHBORS_METRIC, "within radius");
+                ui.radio_value(&mut params.neighbor_mode, NEIGHBORS_TOPOLOGICAL, "k nearest");
+            });
+            if params.neighbor_mode == NEIGHBORS_TOPOLOGICAL {
+                ui.add(egui::Slider::new(&mut params.k_neighbors, 1..=MAX_K_NEIGHBORS).text("k"));
+            } else {
+                rule_radius(ui, "alignment", &mut params.alignment_distance, 0.5, &mut params.alignment_falloff);
+                rule_radius(ui, "cohesion", &mut params.cohesion_distance, 0.5, &mut params.cohesion_falloff);
+            }
+            rule_radius(ui, "separation", &mut params.separation_distance, 0.2, &mut params.separation_falloff);
+            model_sliders(ui, *model, params);
+            ui.add(egui::Slider::new(&mut params.max_speed, 0.0..=2.0).text("max speed"));
+            ui.add(egui::Slider::new(&mut params.min_speed, 0.0..=1.0).text("min speed"));
+            ui.add(egui::Slider::new(&mut params.max_force, 0.0..=50.0).text("max force (0 = none)"));
+            ui.add(egui::Slider::new(&mut params.max_turn_rate, 0.0..=30.0).text("max turn rate (0 = none)"));
+            ui.add(egui::Slider::new(&mut params.drag, 0.0..=5.0).text("drag"));
+            ui.add(egui::Slider::new(&mut params.delta_t, 0.001..=0.25).text("time step"));
+            egui::ComboBox::from_label("integrator")
+                .selected_text(INTEGRATOR_NAMES[params.integrator as usize])
+                .show_ui(ui, |ui| {
+                    for (value, name) in INTEGRATOR_NAMES.iter().enumerate() {
+                        ui.selectable_value(&mut params.integrator, value as u32, *name);
+                    }
+                });
+            ui.add(angle_slider(&mut params.fov).text("field of view"));
+            ui.add(angle_slider(&mut params.blind_spot).text("blind spot"));
+            if sim.space.is_3d() {
+                ui.label("Selecting a boid and its perception overlay are 2D only");
+            }
+            ui.add(egui::Slider::new(&mut params.wander, 0.0..=0.1).text("wander"));
+            if params.wander > 0.0 {
+                ui.add(egui::Slider::new(&mut params.wander_rate, 0.0..=10.0).text("wander rate"));
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut params.seed).prefix("seed: "));
+                    if ui.button("New seed").clicked() {
+                        params.seed = rand::random();
+                    }
+                });
+            }
+            ui.separator();
+
+            egui::CollapsingHeader::new("Flow field").show(ui, |ui| flow_panel(ui, &mut sim.flow));
+            egui::CollapsingHeader::new("Forces").show(ui, |ui| forces_panel(ui, &mut sim.forces));
+            egui::CollapsingHeader::new("Paths").show(ui, |ui| paths_panel(ui, &mut sim.paths));
+            egui::CollapsingHeader::new("Arena").show(ui, |ui| arena_panel(ui, &mut sim.arena));
+            egui::CollapsingHeader::new("Leaders").show(ui, |ui| leaders_panel(ui, &mut sim.leaders, sim.debug.selected));
+            egui::CollapsingHeader::new("Emitters and sinks").show(ui, |ui| population_panel(ui, &mut sim.lifecycle, sim.space));
+            egui::CollapsingHeader::new("Post-processing").show(ui, |ui| post_panel(ui, &mut sim.post_config));
+            egui::CollapsingHeader::new("Extra force").show(ui, |ui| extra_force_panel(ui, sim.extra_force.as_deref(), actions));
+            ui.separator();
+
+            // the value being typed is kept in egui's memory until it's applied
+            let id = ui.id().with("population");
+            let mut count = ui.data_mut(|data| *data.get_temp_mut_or(id, sim.population));
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut count).range(1..=max_population).speed(100));
+                if ui.button("Set population").clicked() {
+                    actions.set_population = Some(count);
+                }
+            });
+            ui.data_mut(|data| data.insert_temp(id, count));
+            ui.horizontal(|ui| {
+                ui.label("new boids:");
+                for distribution in SpawnDistribution::ALL {
+                    ui.radio_value(&mut sim.lifecycle.distribution, distribution, distribution.name());
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button(if sim.paused { "Resume" } else { "Pause" }).clicked() {
+                    sim.paused = !sim.paused;
+                }
+                if ui.button("Reset").clicked() {
+                    actions.reset = true;
+                }
+                if ui.button("Spawn").clicked() {
+                    actions.spawn = true;
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Save scenario").clicked() {
+                    actions.save_scenario = true;
+                }
+            });
+        });
+}
+
+// a rule's radius with a picker for how its contribution fades out towards it
+fn rule_radius(ui: &mut egui::Ui, rule: &str, radius: &mut f32, max: f32, falloff: &mut u32) {
+    ui.horizontal(|ui| {
+        ui.add(egui::Slider::new(radius, 0.0..=max).text(format!("{} radius", rule)));
+        egui::ComboBox::from_id_salt(rule)
+            .selected_text(FALLOFF_NAMES[*falloff as usize])
+            .show_ui(ui, |ui| {
+                for (value, name) in FALLOFF_NAMES.iter().enumerate() {
+                    ui.selectable_value(falloff, value as u32, *name);
+                }
+            });
+    });
+}
+
+// the parameters only `model` reads
+fn model_sliders(ui: &mut egui::Ui, model: BehaviorModel, params: &mut SimParams) {
+    match model {
+        BehaviorModel::Reynolds => {
+            ui.add(egui::Slider::new(&mut params.cohesion_weight, 0.0..=2.0).text("cohesion weight"));
+            ui.add(egui::Slider::new(&mut params.alignment_weight, 0.0..=2.0).text("alignment weight"));
+            ui.add(egui::Slider::new(&mut params.separation_weight, 0.0..=2.0).text("separation weight"));
+        }
+        BehaviorModel::Vicsek | BehaviorModel::Couzin => {
+            ui.add(angle_slider(&mut params.heading_noise).text("noise"));
+        }
+        BehaviorModel::CuckerSmale => {
+            ui.add(egui::Slider::new(&mut params.cucker_smale_strength, 0.0..=20.0).text("coupling"));
+            ui.add(egui::Slider::new(&mut params.cucker_smale_beta, 0.0..=3.0).text("beta"));
+        }
+        BehaviorModel::DOrsogna => {
+            ui.add(egui::Slider::new(&mut params.morse_attraction, 0.0..=0.1).text("attraction"));
+            ui.add(egui::Slider::new(&mut params.morse_attraction_range, 0.001..=0.2).text("attraction range"));
+            ui.add(egui::Slider::new(&mut params.morse_repulsion, 0.0..=0.1).text("repulsion"));
+            ui.add(egui::Slider::new(&mut params.morse_repulsion_range, 0.001..=0.2).text("repulsion range"));
+            ui.add(egui::Slider::new(&mut params.propulsion, 0.0..=5.0).text("propulsion"));
+            ui.add(egui::Slider::new(&mut params.friction, 0.0..=50.0).text("friction"));
+        }
+    }
+}
+
+// an angle kept in radians, shown in degrees
+fn angle_slider(angle: &mut f32) -> egui::Slider<'_> {
+    egui::Slider::new(angle, 0.0..=std::f32::consts::TAU)
+        .custom_formatter(|value, _| format!("{:.0}°", value.to_degrees()))
+        .custom_parser(|text| text.trim_end_matches('°').parse::<f64>().ok().map(f64::to_radians))
+}
+
+// wind is set as a direction in the xy plane and a speed. Fields are loaded by dropping a file on the window
+fn flow_panel(ui: &mut egui::Ui, flow: &mut FlowField) {
+    let params = &mut flow.params;
+    ui.horizontal(|ui| {
+        for (kind, name) in FLOW_NAMES.iter().enumerate() {
+            ui.radio_value(&mut params.kind, kind as u32, *name);
+        }
+    });
+    ui.add(egui::Slider::new(&mut params.strength, 0.0..=1.0).text("strength"));
+    match params.kind {
+        FLOW_WIND => {
+            let mut angle = params.wind[1].atan2(params.wind[0]).rem_euclid(std::f32::consts::TAU);
+            let mut speed = (params.wind[0] * params.wind[0] + params.wind[1] * params.wind[1]).sqrt();
+            ui.add(angle_slider(&mut angle).text("direction"));
+            ui.add(egui::Slider::new(&mut speed, 0.0..=2.0).text("speed"));
+            params.wind = [angle.cos() * speed, angle.sin() * speed, params.wind[2]];
+        }
+        FLOW_CURL_NOISE => {
+            ui.add(egui::Slider::new(&mut params.noise_scale, 0.1..=10.0).logarithmic(true).text("noise scale"));
+            ui.add(egui::Slider::new(&mut params.time_scale, 0.0..=5.0).text("time scale"));
+        }
+        _ => {}
+    }
+    ui.checkbox(&mut flow.show_arrows, "show arrows (2D)");
+}
+
+// the list of sources and the settings of the selected one
+fn forces_panel(ui: &mut egui::Ui, forces: &mut Forces) {
+    ui.checkbox(&mut forces.editing, "edit with the mouse (2D)");
+    if forces.editing {
+        ui.horizontal(|ui| {
+            ui.label("place:");
+            for kind in ForceKind::ALL {
+                ui.radio_value(&mut forces.place_kind, kind, kind.name());
+            }
+        });
+    }
+    for (i, source) in forces.sources.iter().enumerate() {
+        let position = source.position;
+        let text = format!("{}: {} at ({:.2}, {:.2})", i + 1, source.kind.name(), position[0], position[1]);
+        if ui.selectable_label(forces.selected == Some(i), text).clicked() {
+            forces.selected = Some(i);
+        }
+    }
+    if !forces.sources.is_empty() && ui.button("Remove all").clicked() {
+        forces.set_sources(Vec::new());
+    }
+    let Some(index) = forces.selected.filter(|&i| i < forces.sources.len()) else {
+        return;
+    };
+    ui.separator();
+    let source = &mut forces.sources[index];
+    ui.horizontal(|ui| {
+        for kind in ForceKind::ALL {
+            ui.radio_value(&mut source.kind, kind, kind.name());
+        }
+    });
+    // negative strengths turn vortices clockwise
+    let min_strength = if source.kind == ForceKind::Vortex { -1.0 } else { 0.0 };
+    ui.add(egui::Slider::new(&mut source.strength, min_strength..=1.0).text("strength"));
+    rule_radius(ui, "force", &mut source.radius, 1.0, &mut source.falloff);
+    ui.add(egui::Slider::new(&mut source.orbit_radius, 0.0..=0.8).text("orbit radius"));
+    ui.add(egui::Slider::new(&mut source.orbit_speed, -3.0..=3.0).text("orbit speed"));
+    ui.add(egui::Slider::new(&mut source.pulse, 0.0..=1.0).text("pulse"));
+    ui.add(egui::Slider::new(&mut source.pulse_speed, 0.0..=10.0).text("pulse speed"));
+    if ui.button("Remove").clicked() {
+        forces.remove(index);
+    }
+}
+
+// the user's WGSL steering rule, if any
+fn extra_force_panel(ui: &mut egui::Ui, extra_force: Option<&str>, actions: &mut PanelActions) {
+    match extra_force {
+        Some(source) => {
+            ui.label(format!("extra_force in use, {} lines", source.lines().count()));
+            ui.code(source);
+            if ui.button("Remove").clicked() {
+                actions.clear_extra_force = true;
+            }
+        }
+        None => {
+            ui.label("drop a .wgsl file defining");
+            ui.code("fn extra_force(boid: Boid, neighbor: Boid, d: f32)");
+            ui.label("on the window to add it to the steering");
+        }
+    }
+}
+
+// each path's followers and steering, with drawing taking over the mouse
+fn paths_panel(ui: &mut egui::Ui, paths: &mut Paths) {
+    ui.checkbox(&mut paths.drawing, "draw with the mouse (2D)");
+    if paths.drawing {
+        ui.label("Drag to draw a path, end it at its start to make a loop");
+    }
+    let mut remove = None;
+    for (i, path) in paths.paths.iter_mut().enumerate() {
+        egui::CollapsingHeader::new(format!("path {}: {} points", i + 1, path.points.len()))
+            .id_salt(("path", i))
+            .show(ui, |ui| {
+                ui.checkbox(&mut path.looped, "loop");
+                let [first, last] = &mut path.followers;
+                ui.add(egui::Slider::new(first, 0.0..=1.0).text("followers from"));
+                ui.add(egui::Slider::new(last, 0.0..=1.0).text("followers to"));
+                ui.add(egui::Slider::new(&mut path.weight, 0.0..=2.0).text("weight"));
+                ui.add(egui::Slider::new(&mut path.lookahead, 0.0..=0.5).text("lookahead"));
+                ui.add(egui::Slider::new(&mut path.arrive_radius, 0.0..=0.5).text("arrive radius"));
+                if ui.button("Remove").clicked() {
+                    remove = Some(i);
+                }
+            });
+    }
+    if let Some(i) = remove {
+        paths.paths.remove(i);
+    }
+    if !paths.paths.is_empty() && ui.button("Remove all").clicked() {
+        paths.paths.clear();
+    }
+}
+
+// the shape of the 2D world and how hard the boids keep off its walls
+fn arena_panel(ui: &mut egui::Ui, arena: &mut Arena) {
+    ui.horizontal(|ui| {
+        for shape in ArenaShape::ALL {
+            ui.radio_value(&mut arena.settings.shape, shape, shape.name());
+        }
+    });
+    match arena.settings.shape {
+        ArenaShape::Square => {
+            ui.label("Boids wrap around the edges");
+            return;
+        }
+        ArenaShape::Circle => {
+            ui.add(egui::Slider::new(&mut arena.settings.radius, 0.1..=1.0).text("radius"));
+        }
+        ArenaShape::Ellipse => {
+            let [x, y] = &mut arena.settings.radii;
+            ui.add(egui::Slider::new(x, 0.1..=1.0).text("x radius"));
+            ui.add(egui::Slider::new(y, 0.1..=1.0).text("y radius"));
+        }
+        ArenaShape::Polygon => {
+            ui.checkbox(&mut arena.drawing, "draw with the mouse (2D)");
+            if arena.drawing {
+                ui.label("Drag around the area to fence in, the polygon closes when released");
+            }
+            ui.label(format!("{} corners", arena.settings.points.len()));
+        }
+    }
+    ui.add(egui::Slider::new(&mut arena.settings.margin, 0.0..=0.3).text("wall margin"));
+    ui.add(egui::Slider::new(&mut arena.settings.strength, 0.0..=50.0).text("wall strength"));
+}
+
+// how many boids lead, where they go and how much the others follow them.
+// Single boids are made leaders by selecting them with a click
+fn leaders_panel(ui: &mut egui::Ui, leaders: &mut Leaders, selected: Option<u32>) {
+    let mut count = leaders.settings.boids.len() as u32;
+    if ui.add(egui::Slider::new(&mut count, 0..=100).text("first boids lead")).changed() {
+        leaders.set_count(count);
+    }
+    if let Some(index) = selected {
+        let text = if leaders.is_leader(index) { "Make selected boid a peer" } else { "Make selected boid a leader" };
+        if ui.button(text).clicked() {
+            leaders.toggle(index);
+        }
+    }
+    let settings = &mut leaders.settings;
+    ui.horizontal(|ui| {
+        for mode in LeaderMode::ALL {
+            ui.radio_value(&mut settings.mode, mode, mode.name());
+        }
+    });
+    if settings.mode == LeaderMode::Mouse {
+        ui.label("Leaders head for the cursor (2D)");
+    } else {
+        ui.add(egui::Slider::new(&mut settings.speed, 0.0..=2.0).text("speed"));
+    }
+    ui.add(egui::Slider::new(&mut settings.influence, 1.0..=20.0).text("influence"));
+    ui.add(egui::Slider::new(&mut settings.steering, 0.0..=1.0).text("steering"));
+    ui.add(egui::Slider::new(&mut settings.flocking, 0.0..=1.0).text("flocking"));
+}
+
+// regions adding and removing boids, placed with sliders
+fn population_panel(ui: &mut egui::Ui, lifecycle: &mut Population, space: Space) {
+    let mut remove = None;
+    for (i, region) in lifecycle.regions.iter_mut().enumerate() {
+        egui::CollapsingHeader::new(format!("{} {}", region.kind.name(), i + 1))
+            .id_salt(("region", i))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for kind in RegionKind::ALL {
+                        ui.radio_value(&mut region.kind, kind, kind.name());
+                    }
+                });
+                let axes = if space.is_3d() { 3 } else { 2 };
+                for (axis, name) in ["x", "y", "z"].iter().enumerate().take(axes) {
+                    ui.add(egui::Slider::new(&mut region.position[axis], -1.0..=1.0).text(*name));
+                }
+                ui.add(egui::Slider::new(&mut region.radius, 0.01..=0.5).text("radius"));
+                if region.kind == RegionKind::Emitter {
+                    ui.add(egui::Slider::new(&mut region.rate, 0.0..=5000.0).logarithmic(true).text("boids per second"));
+                }
+                if ui.button("Remove").clicked() {
+                    remove = Some(i);
+                }
+            });
+    }
+    if let Some(i) = remove {
+        lifecycle.regions.remove(i);
+    }
+    if lifecycle.regions.len() < MAX_REGIONS {
+        ui.horizontal(|ui| {
+            for kind in RegionKind::ALL {
+                if ui.button(format!("Add {}", kind.name())).clicked() {
+                    lifecycle.regions.push(Region { kind, ..Default::default() });
+                }
+            }
+        });
+    }
+}
+
+// one row per stage in the order they run, with buttons to move it up or down the chain
+fn post_panel(ui: &mut egui::Ui, post: &mut PostConfig) {
+    let mut swap = None;
+    let count = post.stages.len();
+    for (i, stage) in post.stages.iter_mut().enumerate() {
+        ui.push_id(i, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut stage.enabled, stage.effect.name());
+                if ui.add_enabled(i > 0, egui::Button::new("^")).clicked() {
+                    swap = Some(i - 1);
+                }
+                if ui.add_enabled(i + 1 < count, egui::Button::new("v")).clicked() {
+                    swap = Some(i);
+                }
+            });
+            if !stage.enabled {
+                return;
+            }
+            match &mut stage.effect {
+                PostEffect::Trails { persistence } => {
+                    ui.add(egui::Slider::new(persistence, 0.0..=0.99).text("persistence"));
+                }
+                PostEffect::Bloom { threshold, intensity, radius } => {
+                    ui.add(egui::Slider::new(threshold, 0.0..=2.0).text("threshold"));
+                    ui.add(egui::Slider::new(intensity, 0.0..=4.0).text("intensity"));
+                    ui.add(egui::Slider::new(radius, 0.5..=4.0).text("radius"));
+                }
+                PostEffect::ToneMap { exposure } => {
+                    ui.add(egui::Slider::new(exposure, 0.1..=4.0).text("exposure"));
+                }
+                PostEffect::Vignette { strength, radius } => {
+                    ui.add(egui::Slider::new(strength, 0.0..=1.0).text("strength"));
+                    ui.add(egui::Slider::new(radius, 0.0..=1.0).text("radius"));
+                }
+            }
+        });
+    }
+    if let Some(i) = swap {
+        post.stages.swap(i, i + 1);
+    }
+}
+
+fn translate_key(key: &Key) -> Option<egui::Key> {
+    let Key::Named(named) = key else {
+        return None;
+    };
+    Some(match named {
+        NamedKey::Backspace => egui::Key::Backspace,
+        NamedKey::Delete => egui::Key::Delete,
+        NamedKey::Enter => egui::Key::Enter,
+        NamedKey::Tab => egui::Key::Tab,
+        NamedKey::Escape => egui::Key::Escape,
+        NamedKey::Home => egui::Key::Home,
+        NamedKey::End => egui::Key::End,
+        NamedKey::ArrowLeft => egui::Key::ArrowLeft,
+        NamedKey::ArrowRight => egui::Key::ArrowRight,
+        NamedKey::ArrowUp => egui::Key::ArrowUp,
+        NamedKey::ArrowDown => egui::Key::ArrowDown,
+        _ => return None,
+    })
+}
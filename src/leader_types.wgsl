@@ -0,0 +1,11 @@
+// LeaderParams struct that matches leaders.rs, read by leaders.wgsl
+
+struct LeaderParams {
+    mode: u32,
+    influence: f32,
+    steering: f32,
+    flocking: f32,
+    goal: vec3<f32>,
+    speed: f32,
+    time: f32,
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+
+// bits of a boid's entry in the flag buffer, must match leaders.wgsl
+pub const BOID_LEADER: u32 = 1;
+
+// How leaders pick where to go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LeaderMode {
+    // a figure eight through the middle of the world, leaders spread out along it
+    #[default]
+    Scripted,
+    // towards the mouse cursor (2D)
+    Mouse,
+    // a heading that drifts at random, different for every leader
+    Wander,
+}
+
+impl LeaderMode {
+    pub const ALL: [LeaderMode; 3] = [LeaderMode::Scripted, LeaderMode::Mouse, LeaderMode::Wander];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LeaderMode::Scripted => "scripted",
+            LeaderMode::Mouse => "mouse",
+            LeaderMode::Wander => "wander",
+        }
+    }
+}
+
+// Which boids lead and how, as edited in the panel and saved in scenario files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderSettings {
+    // indices of the leading boids in the instance buffers
+    pub boids: Vec<u32>,
+    pub mode: LeaderMode,
+    // how much more a leader counts than a peer in its neighbors' alignment and cohesion
+    pub influence: f32,
+    // how hard leaders steer towards where they're going
+    pub steering: f32,
+    // how much leaders still align with and stay close to their neighbors, 0 ignores them
+    pub flocking: f32,
+    // how fast the scripted route is followed and the wandering heading changes
+    pub speed: f32,
+}
+
+impl Default for LeaderSettings {
+    fn default() -> Self {
+        Self {
+            boids: Vec::new(),
+            mode: LeaderMode::Scripted,
+            influence: 5.0,
+            steering: 0.1,
+            flocking: 0.2,
+            speed: 0.3,
+        }
+    }
+}
+
+// LeaderParams struct that matches leader_types.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LeaderParams {
+    pub(crate) mode: u32,
+    pub(crate) influence: f32,
+    pub(crate) steering: f32,
+    pub(crate) flocking: f32,
+    pub(crate) goal: [f32; 3],
+    pub(crate) speed: f32,
+    pub(crate) time: f32,
+    pub(crate) _pad: [u32; 3],
+}
+
+// Boids flagged as leaders, which their neighbors follow more than their peers while they
+// head somewhere of their own. The flags live in a buffer with one u32 per boid, read by the
+// compute kernel and by the boid shaders to color the leaders.
+pub struct Leaders {
+    pub settings: LeaderSettings,
+    // where the mouse mode leads to
+    pub goal: [f32; 3],
+    // seconds of simulation time, drives the scripted route and the wandering
+    pub time: f32,
+    // the flags need uploading again
+    dirty: bool,
+    max_instances: u32,
+    pub flag_buffer: wgpu::Buffer,
+    pub params_buffer: wgpu::Buffer,
+}
+
+impl Leaders {
+    pub fn new(device: &wgpu::Device, max_instances: u32) -> Self {
+        let flag_buffer = create_flag_buffer(device, max_instances);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Leader Params Buffer"),
+            size: std::mem::size_of::<LeaderParams>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            settings: LeaderSettings::default(),
+            goal: [0.0; 3],
+            time: 0.0,
+            dirty: true,
+            max_instances,
+            flag_buffer,
+            params_buffer,
+        }
+    }
+
+    // the flag and params bindings of the compute kernel's layout
+    pub fn layout_entries(visibility: wgpu::ShaderStages) -> [wgpu::BindGroupLayoutEntry; 2] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: 10,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 11,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<LeaderParams>() as _),
+                },
+                count: None,
+            },
+        ]
+    }
+
+    // per-instance flags for the boid shaders, at location 5 in both of them
+    pub fn flag_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![5 => Uint32];
+        wgpu::VertexBufferLayout {
+            array_stride: 4,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBS,
+        }
+    }
+
+    pub fn is_leader(&self, index: u32) -> bool {
+        self.settings.boids.contains(&index)
+    }
+
+    // make the first `count` boids the leaders
+    pub fn set_count(&mut self, count: u32) {
+        self.settings.boids = (0..count.min(self.max_instances)).collect();
+        self.dirty = true;
+    }
+
+    // turn a single boid into a leader or back into a peer
+    pub fn toggle(&mut self, index: u32) {
+        match self.settings.boids.iter().position(|&boid| boid == index) {
+            Some(i) => {
+                self.settings.boids.remove(i);
+            }
+            None if index < self.max_instances => self.settings.boids.push(index),
+            None => {}
+        }
+        self.dirty = true;
+    }
+
+    // replace the settings, e.g. with the ones from a scenario, restarting the route
+    pub fn set_settings(&mut self, mut settings: LeaderSettings) {
+        settings.boids.retain(|&boid| boid < self.max_instances);
+        self.settings = settings;
+        self.time = 0.0;
+        self.dirty = true;
+    }
+
+    // flags for `max_instances` boids, uploaded again on the next write. Leaders past the end are dropped
+    pub fn resize(&mut self, device: &wgpu::Device, max_instances: u32) {
+        self.flag_buffer = create_flag_buffer(device, max_instances);
+        self.max_instances = max_instances;
+        self.settings.boids.retain(|&boid| boid < max_instances);
+        self.dirty = true;
+    }
+
+    pub fn advance(&mut self, delta_t: f32) {
+        self.time += delta_t;
+    }
+
+    // upload the params, and the flags if the leaders changed
+    pub fn write(&mut self, queue: &wgpu::Queue) {
+        let params = LeaderParams {
+            mode: self.settings.mode as u32,
+            influence: self.settings.influence,
+            steering: self.settings.steering,
+            flocking: self.settings.flocking,
+            goal: self.goal,
+            speed: self.settings.speed,
+            time: self.time,
+            _pad: [0; 3],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+        if self.dirty {
+            let mut flags = vec![0u32; self.max_instances as usize];
+            for &boid in &self.settings.boids {
+                flags[boid as usize] |= BOID_LEADER;
+            }
+            queue.write_buffer(&self.flag_buffer, 0, bytemuck::cast_slice(&flags));
+            self.dirty = false;
+        }
+    }
+}
+
+fn create_flag_buffer(device: &wgpu::Device, max_instances: u32) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Boid Flag Buffer"),
+        size: max_instances as u64 * 4,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,54 @@
+// Leader boids, imported by compute.wgsl and compute3d.wgsl.
+// Uses `params` from the kernel importing it
+
+#import leader_types.wgsl
+#import math.wgsl
+#import random.wgsl
+
+// must match leaders.rs
+const BOID_LEADER : u32 = 1u;
+const LEADER_MOUSE : u32 = 1u;
+const LEADER_WANDER : u32 = 2u;
+
+// one u32 of BOID_* bits per boid
+@group(0) @binding(10)
+var<storage, read> boid_flags: array<u32>;
+@group(0) @binding(11)
+var<uniform> leaders: LeaderParams;
+
+fn is_leader(i: u32) -> bool {
+    return (boid_flags[i] & BOID_LEADER) != 0u;
+}
+
+// how much boid `j` counts in its neighbors' alignment and cohesion
+fn leader_weight(j: u32) -> f32 {
+    return select(1.0, leaders.influence, is_leader(j));
+}
+
+// steering of leader `i` towards where its mode leads, already weighted.
+// `volume` lets the route and the wandering leave the xy plane
+fn leader_steering(i: u32, pos: vec3<f32>, vel: vec3<f32>, volume: bool) -> vec3<f32> {
+    var heading: vec3<f32>;
+    switch leaders.mode {
+        case LEADER_MOUSE: {
+            heading = leaders.goal - pos;
+        }
+        case LEADER_WANDER: {
+            let t = leaders.time * leaders.speed;
+            let yaw = value_noise(pcg(i), t) * TAU;
+            let pitch = select(0.0, value_noise(pcg(i + 0x9e3779b9u), t) * 0.5, volume);
+            heading = vec3<f32>(cos(yaw) * cos(pitch), sin(yaw) * cos(pitch), sin(pitch));
+        }
+        default: {
+            // a figure eight, each leader starting somewhere else along it
+            let phase = leaders.time * leaders.speed + hash01(i) * TAU;
+            let route = vec3<f32>(0.6 * sin(phase), 0.4 * sin(2.0 * phase), select(0.0, 0.4 * cos(phase), volume));
+            heading = route - pos;
+        }
+    }
+    let len = length(heading);
+    if (len == 0.0) {
+        return vec3<f32>(0.0);
+    }
+    return (heading / len * params.max_speed - vel) * leaders.steering;
+}
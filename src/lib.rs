@@ -3,4 +3,26 @@
 pub mod engine;
 pub mod state;
 pub mod vertex;
-pub mod boid;
\ No newline at end of file
+pub mod arena;
+pub mod boid;
+pub mod camera;
+pub mod capture;
+pub mod debug;
+pub mod flow;
+pub mod forces;
+pub mod globe;
+pub mod grid;
+pub mod gui;
+pub mod leaders;
+pub mod models;
+pub mod params;
+pub mod paths;
+pub mod population;
+pub mod postprocess;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod record;
+pub mod reference;
+pub mod scenario;
+pub mod shaders;
+pub mod sim;
+pub mod snippet;
\ No newline at end of file
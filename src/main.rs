@@ -1,5 +1,33 @@
 use boids_sim::engine;
+#[cfg(not(target_arch = "wasm32"))]
+use boids_sim::sim::Space;
 
 fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if args.iter().any(|arg| arg == "--headless") {
+            let options = engine::HeadlessOptions::from_args(&args).unwrap_or_else(|err| {
+                eprintln!("{}\n{}", err, engine::HeadlessOptions::USAGE);
+                std::process::exit(2);
+            });
+            if let Err(err) = pollster::block_on(engine::run_headless(options)) {
+                eprintln!("Headless run failed: {}", err);
+                std::process::exit(1);
+            }
+            return;
+        }
+        let space = if args.iter().any(|arg| arg == "--sphere") {
+            Space::Sphere
+        } else if args.iter().any(|arg| arg == "--3d") {
+            Space::Volume
+        } else {
+            Space::Plane
+        };
+        // rebuild the pipelines whenever a shader file in the source tree is saved
+        let watch_shaders = args.iter().any(|arg| arg == "--watch-shaders");
+        pollster::block_on(engine::run_with(space, watch_shaders));
+    }
+    #[cfg(target_arch = "wasm32")]
     pollster::block_on(engine::run());
 }
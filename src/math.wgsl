@@ -0,0 +1,4 @@
+// Constants shared by the shaders
+
+const PI : f32 = 3.141592654;
+const TAU : f32 = 6.283185307;
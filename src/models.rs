@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sim::Space;
+
+// Collective motion models the kernels can run, all reading and writing the same boid buffers and drawn the same way.
+// Each gets its own compute pipeline, built from the shared kernel with the MODEL override of models.wgsl,
+// and its parameters are in SimParams next to the Reynolds ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BehaviorModel {
+    // separation, alignment and cohesion
+    #[default]
+    Reynolds,
+    // noisy alignment at constant speed
+    Vicsek,
+    // zones of repulsion, orientation and attraction
+    Couzin,
+    // velocity matching weighted by distance
+    CuckerSmale,
+    // self-propulsion and an attraction-repulsion potential
+    DOrsogna,
+}
+
+impl BehaviorModel {
+    pub const ALL: [Self; 5] = [Self::Reynolds, Self::Vicsek, Self::Couzin, Self::CuckerSmale, Self::DOrsogna];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Reynolds => "Reynolds",
+            Self::Vicsek => "Vicsek",
+            Self::Couzin => "Couzin",
+            Self::CuckerSmale => "Cucker–Smale",
+            Self::DOrsogna => "D'Orsogna",
+        }
+    }
+
+    // the name in lower case with dashes, as given on the command line
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Reynolds => "reynolds",
+            Self::Vicsek => "vicsek",
+            Self::Couzin => "couzin",
+            Self::CuckerSmale => "cucker-smale",
+            Self::DOrsogna => "dorsogna",
+        }
+    }
+
+    // value of the MODEL override, must match the MODEL_* constants in models.wgsl
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+}
+
+// The compute pipeline of every model, all created up front so a shader that one of them can't
+// build is turned down inside the caller's error scope rather than the first time that model runs
+pub struct ModelKernels {
+    layout: wgpu::PipelineLayout,
+    // the sphere mode runs the 3D kernel with the SPHERE override of sphere.wgsl set
+    space: Space,
+    pipelines: Vec<wgpu::ComputePipeline>,
+}
+
+impl ModelKernels {
+    // `extra_force` is whether the module has a user extra_force to call
+    pub fn new(device: &wgpu::Device, module: &wgpu::ShaderModule, layout: wgpu::PipelineLayout, space: Space, extra_force: bool) -> Self {
+        // the 2D kernel has no SPHERE override, constants a shader doesn't declare are ignored
+        let sphere = space == Space::Sphere;
+        let pipelines = BehaviorModel::ALL.iter().map(|model| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(model.name()),
+            layout: Some(&layout),
+            module,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &[("MODEL", model.id() as f64), ("EXTRA_FORCE", extra_force as u32 as f64), ("SPHERE", sphere as u32 as f64)],
+                ..Default::default()
+            },
+            cache: None,
+        })).collect();
+        Self { layout, space, pipelines }
+    }
+
+    // kernels from another build of the compute shader, with the same bindings
+    pub fn rebuilt(&self, device: &wgpu::Device, module: &wgpu::ShaderModule, extra_force: bool) -> Self {
+        Self::new(device, module, self.layout.clone(), self.space, extra_force)
+    }
+
+    pub fn pipeline(&self, model: BehaviorModel) -> &wgpu::ComputePipeline {
+        &self.pipelines[model.id() as usize]
+    }
+}
@@ -0,0 +1,187 @@
+// The collective motion models besides Reynolds (see models.rs), imported by compute.wgsl and compute3d.wgsl.
+// Each gives the acceleration of boid `i` at `pos` with `vel` from the same neighbors the Reynolds rules look at:
+// the first `candidates` boids within reach in metric mode, the k nearest in topological mode.
+// Both kernels define `neighbor(n, ahead, pos)`, which reads candidate `n` as vec3s moved on by `ahead` seconds,
+// where the boid at `pos` sees it (along the surface on the sphere, see sphere.wgsl)
+
+#import flight.wgsl
+
+// the model this pipeline runs, set by models.rs when it's created
+override MODEL : u32 = 0u;
+const MODEL_REYNOLDS : u32 = 0u;
+const MODEL_VICSEK : u32 = 1u;
+const MODEL_COUZIN : u32 = 2u;
+const MODEL_CUCKER_SMALE : u32 = 3u;
+const MODEL_DORSOGNA : u32 = 4u;
+// whether the user's extra_force (see snippet.rs) is added to the steering of every model
+override EXTRA_FORCE : bool = false;
+
+struct Neighbor {
+    index: u32,
+    pos: vec3<f32>,
+    vel: vec3<f32>,
+}
+
+// whether a neighbor at `dist` counts for a rule reaching `radius`, always in topological mode
+fn in_reach(dist: f32, radius: f32) -> bool {
+    return params.neighbor_mode == NEIGHBORS_TOPOLOGICAL || dist <= radius;
+}
+
+// cosine of the angle between `vel` and the direction from `pos` to `other`
+fn heading_cos(pos: vec3<f32>, vel: vec3<f32>, other: vec3<f32>, dist: f32) -> f32 {
+    let heading_len = length(vel);
+    if (heading_len == 0.0 || dist == 0.0) {
+        return 1.0;
+    }
+    return clamp(dot(vel, other - pos) / (heading_len * dist), -1.0, 1.0);
+}
+
+// unit `dir` turned by a random angle of up to half `params.heading_noise` either way, drawn anew every step
+fn noisy_heading(i: u32, dir: vec3<f32>, volume: bool) -> vec3<f32> {
+    if (params.heading_noise == 0.0) {
+        return dir;
+    }
+    let key = pcg(params.seed ^ pcg(i ^ pcg(params.step)));
+    let angle = (hash01(key) - 0.5) * params.heading_noise;
+    var side = vec3<f32>(-dir.y, dir.x, 0.0);
+    if (volume) {
+        // any way across the heading
+        let r = vec3<f32>(hash01(key + 1u), hash01(key + 2u), hash01(key + 3u)) * 2.0 - 1.0;
+        side = r - dir * dot(dir, r);
+    }
+    return dir * cos(angle) + safe_normalize(side, vec3<f32>(0.0)) * sin(angle);
+}
+
+// the heading models fly at constant speed: this acceleration gets `vel` to `desired` in one step
+fn steer_to(desired: vec3<f32>, vel: vec3<f32>) -> vec3<f32> {
+    return (desired - vel) / params.delta_t;
+}
+
+// Vicsek: head the average way of everyone within the alignment radius, itself included, plus noise
+fn vicsek_steering(i: u32, pos: vec3<f32>, vel: vec3<f32>, ahead: f32, candidates: u32, volume: bool) -> vec3<f32> {
+    let fov_cos = cos(params.fov * 0.5);
+    let blind_cos = -cos(params.blind_spot * 0.5);
+    var sum = safe_normalize(vel, vec3<f32>(0.0));
+    for (var n = 0u; n < candidates; n++) {
+        let other = neighbor(n, ahead, pos);
+        let dist = distance(other.pos, pos);
+        if (other.index == i || !in_reach(dist, params.alignment_distance)) {
+            continue;
+        }
+        let cos_angle = heading_cos(pos, vel, other.pos, dist);
+        if ((params.blind_spot > 0.0 && cos_angle < blind_cos) || (params.fov < TAU && cos_angle < fov_cos)) {
+            continue;
+        }
+        sum += safe_normalize(other.vel, vec3<f32>(0.0));
+    }
+    let dir = noisy_heading(i, safe_normalize(sum, rest_heading(i, volume)), volume);
+    return steer_to(dir * params.max_speed, vel);
+}
+
+// Couzin: move away from anyone in the zone of repulsion (the separation radius). With nobody there,
+// line up with those in the zone of orientation (the alignment radius) and head for those in the zone
+// of attraction (out to the cohesion radius). Constant speed, turning no faster than the max turn rate
+fn couzin_steering(i: u32, pos: vec3<f32>, vel: vec3<f32>, ahead: f32, candidates: u32, volume: bool) -> vec3<f32> {
+    let fov_cos = cos(params.fov * 0.5);
+    let blind_cos = -cos(params.blind_spot * 0.5);
+    let heading = safe_normalize(vel, rest_heading(i, volume));
+    var repulsion = vec3<f32>(0.0);
+    var orientation = heading;
+    var attraction = vec3<f32>(0.0);
+    var repelled = false;
+    var oriented = false;
+    var attracted = false;
+    for (var n = 0u; n < candidates; n++) {
+        let other = neighbor(n, ahead, pos);
+        let offset = other.pos - pos;
+        let dist = length(offset);
+        if (other.index == i || dist == 0.0) {
+            continue;
+        }
+        let cos_angle = heading_cos(pos, vel, other.pos, dist);
+        if (params.blind_spot > 0.0 && cos_angle < blind_cos) {
+            continue;
+        }
+        // the zones go by distance in either neighbor mode
+        if (dist < params.separation_distance) {
+            repulsion -= offset / dist;
+            repelled = true;
+            continue;
+        }
+        if (params.fov < TAU && cos_angle < fov_cos) {
+            continue;
+        }
+        if (dist < params.alignment_distance) {
+            orientation += safe_normalize(other.vel, vec3<f32>(0.0));
+            oriented = true;
+        } else if (dist < params.cohesion_distance) {
+            attraction += offset / dist;
+            attracted = true;
+        }
+    }
+    var dir = heading;
+    if (repelled) {
+        dir = safe_normalize(repulsion, heading);
+    } else if (oriented && attracted) {
+        dir = safe_normalize(safe_normalize(orientation, heading) + safe_normalize(attraction, heading), heading);
+    } else if (oriented) {
+        dir = safe_normalize(orientation, heading);
+    } else if (attracted) {
+        dir = safe_normalize(attraction, heading);
+    }
+    dir = noisy_heading(i, dir, volume);
+    return steer_to(dir * params.max_speed, vel);
+}
+
+// Cucker–Smale: match the neighbors' velocities, weighted by 1 / (1 + (r / alignment radius)²)^beta,
+// averaged over everyone within reach
+fn cucker_smale_steering(i: u32, pos: vec3<f32>, vel: vec3<f32>, ahead: f32, candidates: u32) -> vec3<f32> {
+    let perception = max(params.separation_distance, max(params.alignment_distance, params.cohesion_distance));
+    let blind_cos = -cos(params.blind_spot * 0.5);
+    let scale = max(params.alignment_distance, 1.0e-4);
+    var sum = vec3<f32>(0.0);
+    var count = 0u;
+    for (var n = 0u; n < candidates; n++) {
+        let other = neighbor(n, ahead, pos);
+        let dist = distance(other.pos, pos);
+        if (other.index == i || !in_reach(dist, perception)) {
+            continue;
+        }
+        if (params.blind_spot > 0.0 && heading_cos(pos, vel, other.pos, dist) < blind_cos) {
+            continue;
+        }
+        let r = dist / scale;
+        sum += (other.vel - vel) * pow(1.0 + r * r, -params.cucker_smale_beta);
+        count++;
+    }
+    if (count == 0u) {
+        return vec3<f32>(0.0);
+    }
+    return sum * (params.cucker_smale_strength / f32(count));
+}
+
+// D'Orsogna: self-propulsion (alpha - beta |v|²) v towards a cruising speed, and the Morse potential
+// U(r) = Cr exp(-r / lr) - Ca exp(-r / la) between every pair within reach
+fn dorsogna_steering(i: u32, pos: vec3<f32>, vel: vec3<f32>, ahead: f32, candidates: u32) -> vec3<f32> {
+    let perception = max(params.separation_distance, max(params.alignment_distance, params.cohesion_distance));
+    let blind_cos = -cos(params.blind_spot * 0.5);
+    let attraction_range = max(params.morse_attraction_range, 1.0e-4);
+    let repulsion_range = max(params.morse_repulsion_range, 1.0e-4);
+    var force = vec3<f32>(0.0);
+    for (var n = 0u; n < candidates; n++) {
+        let other = neighbor(n, ahead, pos);
+        let offset = pos - other.pos;
+        let dist = length(offset);
+        if (other.index == i || dist == 0.0 || !in_reach(dist, perception)) {
+            continue;
+        }
+        if (params.blind_spot > 0.0 && heading_cos(pos, vel, other.pos, dist) < blind_cos) {
+            continue;
+        }
+        // -dU/dr along the line between the two
+        let push = params.morse_repulsion / repulsion_range * exp(-dist / repulsion_range)
+            - params.morse_attraction / attraction_range * exp(-dist / attraction_range);
+        force += offset / dist * push;
+    }
+    return force + vel * (params.propulsion - params.friction * dot(vel, vel));
+}
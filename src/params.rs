@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+// Simulation parameters shared with the compute shader through a uniform buffer.
+// The field order and types must match the SimParams struct in params_types.wgsl,
+// which the sim_params_match test in shaders.rs checks.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimParams {
+    // radius of each rule, a boid is only looked at if it's within one of them
+    pub alignment_distance: f32,
+    pub separation_distance: f32,
+    pub cohesion_weight: f32,
+    pub alignment_weight: f32,
+    pub separation_weight: f32,
+    pub max_speed: f32,
+    pub delta_t: f32,
+    // slots in use as the CPU last saw them, the kernels read the current count from the population state
+    #[serde(skip)]
+    pub num_boids: u32,
+    // full angle, in radians, of the cone ahead of a boid in which neighbors count for
+    // alignment and cohesion. TAU sees all around
+    pub fov: f32,
+    // full angle of the cone right behind a boid in which nothing is seen, not even for separation
+    pub blind_spot: f32,
+    // NEIGHBORS_METRIC counts every boid within a rule's own radius (separation_distance,
+    // alignment_distance or cohesion_distance), NEIGHBORS_TOPOLOGICAL the `k_neighbors` nearest ones however far they are
+    pub neighbor_mode: u32,
+    pub k_neighbors: u32,
+    pub cohesion_distance: f32,
+    // how each rule's contribution fades towards its radius, one of the FALLOFF_* constants
+    pub separation_falloff: u32,
+    pub alignment_falloff: u32,
+    pub cohesion_falloff: u32,
+    // strength of the random steering every boid adds to the rules, 0 keeps the flock deterministic
+    pub wander: f32,
+    // how many times per second of simulation time the random steering picks a new direction to drift towards
+    pub wander_rate: f32,
+    // seed of all the random steering, the same seed gives the same wandering every run
+    pub seed: u32,
+    // number of steps taken, so the random steering changes over time
+    #[serde(skip)]
+    pub step: u32,
+    // most the steering of all the rules together can change the velocity, in speed per second, 0 for no limit
+    pub max_force: f32,
+    // fastest a boid can turn, in radians per second, 0 for no limit
+    pub max_turn_rate: f32,
+    // cruising speed a boid never drops below, so it can't stop dead. A boid at a standstill is safe either way
+    pub min_speed: f32,
+    // linear drag, the fraction of its velocity a boid loses per second is about this much
+    pub drag: f32,
+    // how a step advances position and velocity, one of the INTEGRATOR_* constants
+    pub integrator: u32,
+    // the rest only matter to the models other than Reynolds, see models.rs
+    // full width, in radians, of the random turn the Vicsek and Couzin boids take every step
+    pub heading_noise: f32,
+    // how fast Cucker–Smale boids match their neighbors' velocity, and how quickly that fades with distance
+    pub cucker_smale_strength: f32,
+    pub cucker_smale_beta: f32,
+    // strength and range of the attractive and repulsive parts of the D'Orsogna (Morse) potential
+    pub morse_attraction: f32,
+    pub morse_attraction_range: f32,
+    pub morse_repulsion: f32,
+    pub morse_repulsion_range: f32,
+    // D'Orsogna self-propulsion and friction, the boids cruise at sqrt(propulsion / friction)
+    pub propulsion: f32,
+    pub friction: f32,
+}
+
+pub const NEIGHBORS_METRIC: u32 = 0;
+pub const NEIGHBORS_TOPOLOGICAL: u32 = 1;
+// the kernel keeps the nearest neighbors in a fixed size array
+pub const MAX_K_NEIGHBORS: u32 = 32;
+
+// full weight inside the radius, nothing outside
+pub const FALLOFF_STEP: u32 = 0;
+pub const FALLOFF_LINEAR: u32 = 1;
+pub const FALLOFF_SMOOTHSTEP: u32 = 2;
+// 1 / distance², scaled to be 1 within a tenth of the radius and 0 at the radius
+pub const FALLOFF_INVERSE_SQUARE: u32 = 3;
+pub const FALLOFF_NAMES: [&str; 4] = ["step", "linear", "smoothstep", "inverse square"];
+
+// moves with the velocity from before the step, then updates it
+pub const INTEGRATOR_EXPLICIT_EULER: u32 = 0;
+// updates the velocity first and moves with the new one
+pub const INTEGRATOR_SEMI_IMPLICIT_EULER: u32 = 1;
+// moves with the old velocity and steering, then averages the steering at both positions
+pub const INTEGRATOR_VELOCITY_VERLET: u32 = 2;
+// midpoint method, steering with what's found half a step ahead
+pub const INTEGRATOR_RK2: u32 = 3;
+pub const INTEGRATOR_NAMES: [&str; 4] = ["explicit Euler", "semi-implicit Euler", "velocity Verlet", "RK2"];
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self {
+            alignment_distance: 0.1,
+            cohesion_distance: 0.1,
+            delta_t: 0.03, // smaller step, smoother motion
+            separation_distance: 0.02, // smaller separation distance
+            cohesion_weight: 0.3, // gentle pull toward center
+            alignment_weight: 0.4, // a bit more to match velocity
+            separation_weight: 0.7, // stronger force to avoid overlap
+            max_speed: 0.4, // keep boids from moving too fast
+            num_boids: 0,
+            fov: std::f32::consts::TAU,
+            blind_spot: 0.0,
+            neighbor_mode: NEIGHBORS_METRIC,
+            k_neighbors: 7, // starlings react to about seven neighbors
+            separation_falloff: FALLOFF_STEP,
+            alignment_falloff: FALLOFF_STEP,
+            cohesion_falloff: FALLOFF_STEP,
+            wander: 0.0,
+            wander_rate: 1.0,
+            seed: 0,
+            step: 0,
+            // no limits by default, the flock flies as it did before they were added
+            max_force: 0.0,
+            max_turn_rate: 0.0,
+            min_speed: 0.0,
+            drag: 0.0,
+            integrator: INTEGRATOR_SEMI_IMPLICIT_EULER,
+            heading_noise: 0.5,
+            cucker_smale_strength: 5.0,
+            cucker_smale_beta: 0.5,
+            morse_attraction: 0.01,
+            morse_attraction_range: 0.03,
+            morse_repulsion: 0.02,
+            morse_repulsion_range: 0.01,
+            propulsion: 1.0,
+            friction: 10.0,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// SimParams struct that matches the uniform buffer in rust (params.rs), with its constants
+
+struct SimParams {
+    alignment_distance: f32,
+    separation_distance: f32,
+    cohesion_weight: f32,
+    alignment_weight: f32,
+    separation_weight: f32,
+    max_speed: f32,
+    delta_t: f32,
+    num_boids: u32,
+    fov: f32,
+    blind_spot: f32,
+    neighbor_mode: u32,
+    k_neighbors: u32,
+    cohesion_distance: f32,
+    separation_falloff: u32,
+    alignment_falloff: u32,
+    cohesion_falloff: u32,
+    wander: f32,
+    wander_rate: f32,
+    seed: u32,
+    step: u32,
+    max_force: f32,
+    max_turn_rate: f32,
+    min_speed: f32,
+    drag: f32,
+    integrator: u32,
+    heading_noise: f32,
+    cucker_smale_strength: f32,
+    cucker_smale_beta: f32,
+    morse_attraction: f32,
+    morse_attraction_range: f32,
+    morse_repulsion: f32,
+    morse_repulsion_range: f32,
+    propulsion: f32,
+    friction: f32,
+}
+
+// must match params.rs
+const NEIGHBORS_TOPOLOGICAL : u32 = 1u;
+const MAX_K_NEIGHBORS : u32 = 32u;
+const FALLOFF_LINEAR : u32 = 1u;
+const FALLOFF_SMOOTHSTEP : u32 = 2u;
+const FALLOFF_INVERSE_SQUARE : u32 = 3u;
+const INTEGRATOR_EXPLICIT_EULER : u32 = 0u;
+const INTEGRATOR_VELOCITY_VERLET : u32 = 2u;
+const INTEGRATOR_RK2 : u32 = 3u;
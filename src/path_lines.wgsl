@@ -0,0 +1,27 @@
+// Lines along the waypoint paths, one instance per path
+
+#import path_types.wgsl
+
+@group(0) @binding(0)
+var<storage, read> paths: PathList;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+// two vertices per segment, the last one of a loop leading back to the first point
+@vertex
+fn path_vs_main(@builtin(vertex_index) index: u32, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let path = paths.infos[instance];
+    let k = (index / 2u + index % 2u) % path.len;
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(paths.points[path.start + k].xy, 0.0, 1.0);
+    out.color = vec3<f32>(1.0, 0.75, 0.3); // amber
+    return out;
+}
+
+@fragment
+fn path_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,23 @@
+// PathInfo and PathList structs that match paths.rs, read by paths.wgsl and path_lines.wgsl
+
+const MAX_PATHS : u32 = 8u;
+
+struct PathInfo {
+    start: u32,
+    len: u32,
+    looped: u32,
+    weight: f32,
+    followers: vec2<f32>,
+    lookahead: f32,
+    arrive_radius: f32,
+}
+
+struct PathList {
+    count: u32,
+    // pads the infos out to 16 bytes in, where paths.rs writes them
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+    infos: array<PathInfo, MAX_PATHS>,
+    points: array<vec4<f32>>,
+}
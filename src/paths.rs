@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+
+use crate::shaders;
+
+// the path buffer has room for this many paths and this many points across all of them
+pub const MAX_PATHS: usize = 8;
+pub const MAX_PATH_POINTS: usize = 1024;
+// bytes before the points in the path buffer: the count padded to 16, then one PathInfo per path
+const HEADER_SIZE: u64 = 16 + (MAX_PATHS * std::mem::size_of::<PathInfo>()) as u64;
+
+// A polyline of waypoints that some of the boids steer along, as saved in scenario files.
+// Followers seek a point a bit further along the path than the closest one, so they keep moving forward,
+// and slow down to arrive at the last point of a path that isn't a loop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WaypointPath {
+    // z is only used in 3D
+    pub points: Vec<[f32; 3]>,
+    // the last point leads back to the first
+    pub looped: bool,
+    // range of boid indices following the path, as fractions of the population.
+    // (0.0, 1.0) is every boid, overlapping ranges follow every path they're in
+    pub followers: [f32; 2],
+    // how much the path steering counts next to the flocking rules
+    pub weight: f32,
+    // how far ahead of the closest point on the path followers aim
+    pub lookahead: f32,
+    // followers slow down within this distance of the end of a path that isn't a loop
+    pub arrive_radius: f32,
+}
+
+impl Default for WaypointPath {
+    fn default() -> Self {
+        Self {
+            points: Vec::new(),
+            looped: false,
+            followers: [0.0, 1.0],
+            weight: 0.5,
+            lookahead: 0.1,
+            arrive_radius: 0.2,
+        }
+    }
+}
+
+// PathInfo struct that matches paths.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PathInfo {
+    // index of the path's first point in the point array
+    start: u32,
+    len: u32,
+    looped: u32,
+    weight: f32,
+    followers: [f32; 2],
+    lookahead: f32,
+    arrive_radius: f32,
+}
+
+// The waypoint paths, uploaded for the compute kernel and drawn as lines in 2D
+pub struct Paths {
+    // at most MAX_PATHS, with no more than MAX_PATH_POINTS points between them
+    pub paths: Vec<WaypointPath>,
+    // the mouse draws new paths instead of selecting boids
+    pub drawing: bool,
+    pub buffer: wgpu::Buffer,
+    line_pipeline: wgpu::RenderPipeline,
+    line_bind_group: wgpu::BindGroup,
+}
+
+impl Paths {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Path Buffer"),
+            size: HEADER_SIZE + (MAX_PATH_POINTS * 16) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut line_entry = Self::layout_entry(wgpu::ShaderStages::VERTEX);
+        line_entry.binding = 0;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[line_entry],
+            label: Some("path lines bind group layout"),
+        });
+        let line_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("path lines bind group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Path Lines Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("path_lines.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Path Lines Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Path Lines Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("path_vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("path_fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            paths: Vec::new(),
+            drawing: false,
+            buffer,
+            line_pipeline,
+            line_bind_group,
+        }
+    }
+
+    // the path buffer binding of the compute kernel's layout
+    pub fn layout_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: 9,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    // number of points that can still be added before the buffer is full
+    pub fn points_left(&self) -> usize {
+        MAX_PATH_POINTS.saturating_sub(self.paths.iter().map(|path| path.points.len()).sum())
+    }
+
+    // start a new path at `position`, followed by every boid. Returns false if there's no room for it
+    pub fn begin(&mut self, position: [f32; 2]) -> bool {
+        if self.paths.len() >= MAX_PATHS || self.points_left() == 0 {
+            return false;
+        }
+        self.paths.push(WaypointPath {
+            points: vec![[position[0], position[1], 0.0]],
+            ..Default::default()
+        });
+        true
+    }
+
+    // add a point to the end of the newest path if it's at least `spacing` from the previous one
+    pub fn extend(&mut self, position: [f32; 2], spacing: f32) {
+        let full = self.points_left() == 0;
+        let Some(path) = self.paths.last_mut() else {
+            return;
+        };
+        let last = path.points[path.points.len() - 1];
+        if !full && (position[0] - last[0]).hypot(position[1] - last[1]) >= spacing {
+            path.points.push([position[0], position[1], 0.0]);
+        }
+    }
+
+    // finish the newest path, closing it into a loop if it ends within `close_distance` of its start.
+    // Paths too short to follow are dropped
+    pub fn end(&mut self, close_distance: f32) {
+        let Some(path) = self.paths.last_mut() else {
+            return;
+        };
+        if path.points.len() < 2 {
+            self.paths.pop();
+            return;
+        }
+        let (first, last) = (path.points[0], path.points[path.points.len() - 1]);
+        if path.points.len() > 2 && (first[0] - last[0]).hypot(first[1] - last[1]) < close_distance {
+            path.points.pop();
+            path.looped = true;
+        }
+    }
+
+    // replace every path, e.g. with the ones from a scenario, dropping whatever doesn't fit in the buffer
+    pub fn set_paths(&mut self, mut paths: Vec<WaypointPath>) {
+        paths.truncate(MAX_PATHS);
+        let mut points_left = MAX_PATH_POINTS;
+        for path in &mut paths {
+            path.points.truncate(points_left);
+            points_left -= path.points.len();
+        }
+        paths.retain(|path| !path.points.is_empty());
+        self.paths = paths;
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue) {
+        let mut infos: [PathInfo; MAX_PATHS] = [bytemuck::Zeroable::zeroed(); MAX_PATHS];
+        let mut points: Vec<[f32; 4]> = Vec::new();
+        for (count, path) in self.paths.iter().enumerate() {
+            let len = path.points.len();
+            infos[count] = PathInfo {
+                start: points.len() as u32,
+                len: len as u32,
+                looped: path.looped as u32,
+                weight: path.weight,
+                followers: path.followers,
+                lookahead: path.lookahead,
+                arrive_radius: path.arrive_radius,
+            };
+            points.extend(path.points.iter().map(|p| [p[0], p[1], p[2], 0.0]));
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&[self.paths.len() as u32, 0, 0, 0]));
+        queue.write_buffer(&self.buffer, 16, bytemuck::cast_slice(&infos));
+        if !points.is_empty() {
+            queue.write_buffer(&self.buffer, HEADER_SIZE, bytemuck::cast_slice(&points));
+        }
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.line_pipeline);
+        render_pass.set_bind_group(0, &self.line_bind_group, &[]);
+        // one instance per path
+        for (i, path) in self.paths.iter().enumerate() {
+            let segments = if path.looped { path.points.len() } else { path.points.len() - 1 };
+            render_pass.draw(0..2 * segments as u32, i as u32..i as u32 + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::{assert_layout, offsets};
+
+    #[test]
+    fn path_info_matches_the_shader() {
+        let fields = offsets!(PathInfo { start, len, looped, weight, followers, lookahead, arrive_radius });
+        assert_layout("path_types.wgsl", "PathInfo", fields, Some(std::mem::size_of::<PathInfo>()));
+    }
+}
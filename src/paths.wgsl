@@ -0,0 +1,89 @@
+// Waypoint path following, imported by compute.wgsl and compute3d.wgsl.
+// Uses `params` from the kernel importing it
+
+#import path_types.wgsl
+
+@group(0) @binding(9)
+var<storage, read> paths: PathList;
+
+// point `k` of path `p`, wrapping around to the first point
+fn path_point(p: u32, k: u32) -> vec3<f32> {
+    let len = paths.infos[p].len;
+    return paths.points[paths.infos[p].start + select(k, k - len, k >= len)].xyz;
+}
+
+// The point `lookahead` further along path `p` than the point closest to `pos`.
+// w is 1 when that runs past the end of a path that isn't a loop, and the target is its last point
+fn path_target(p: u32, pos: vec3<f32>) -> vec4<f32> {
+    let len = paths.infos[p].len;
+    let looped = paths.infos[p].looped != 0u;
+    let segments = select(len - 1u, len, looped);
+    if (segments == 0u) {
+        return vec4<f32>(path_point(p, 0u), 1.0);
+    }
+    // closest point over every segment
+    var best_segment = 0u;
+    var best_point = path_point(p, 0u);
+    var best_dist = 1e30;
+    var a = best_point;
+    for (var k = 0u; k < segments; k++) {
+        let b = path_point(p, k + 1u);
+        let ab = b - a;
+        let t = clamp(dot(pos - a, ab) / max(dot(ab, ab), 1e-12), 0.0, 1.0);
+        let closest = a + ab * t;
+        let dist = distance(pos, closest);
+        if (dist < best_dist) {
+            best_dist = dist;
+            best_segment = k;
+            best_point = closest;
+        }
+        a = b;
+    }
+    // walk forward from there
+    var remaining = paths.infos[p].lookahead;
+    var current = best_point;
+    var k = best_segment;
+    for (var walked = 0u; walked < segments; walked++) {
+        let next = path_point(p, k + 1u);
+        let to_next = distance(current, next);
+        if (remaining <= to_next) {
+            return vec4<f32>(current + (next - current) * (remaining / max(to_next, 1e-6)), 0.0);
+        }
+        remaining -= to_next;
+        current = next;
+        k++;
+        if (k == segments) {
+            if (!looped) {
+                return vec4<f32>(current, 1.0);
+            }
+            k = 0u;
+        }
+    }
+    return vec4<f32>(current, 0.0);
+}
+
+// seek/arrive steering towards every path boid `i` of `total` follows, already weighted
+fn path_steering(i: u32, total: u32, pos: vec3<f32>, vel: vec3<f32>) -> vec3<f32> {
+    var steering = vec3<f32>(0.0);
+    let index = f32(i);
+    for (var p = 0u; p < paths.count; p++) {
+        let followers = paths.infos[p].followers;
+        if (index < followers.x * f32(total) || index >= followers.y * f32(total)) {
+            continue;
+        }
+        let aim = path_target(p, pos);
+        let offset = aim.xyz - pos;
+        let dist = length(offset);
+        if (dist == 0.0) {
+            continue;
+        }
+        // full speed towards the target, slowing down when arriving at the end
+        var speed = params.max_speed;
+        let arrive_radius = paths.infos[p].arrive_radius;
+        if (aim.w != 0.0 && arrive_radius > 0.0) {
+            speed *= min(dist / arrive_radius, 1.0);
+        }
+        steering += (offset / dist * speed - vel) * paths.infos[p].weight;
+    }
+    return steering;
+}
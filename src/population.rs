@@ -0,0 +1,612 @@
+use std::sync::mpsc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{shaders, sim::Space};
+
+// the region buffer has room for this many emitters and sinks
+pub const MAX_REGIONS: usize = 32;
+// bytes before the alive flags in the state buffer
+const STATE_HEADER_SIZE: u64 = std::mem::size_of::<StateHeader>() as u64;
+// bytes before the regions in the region buffer
+const REGION_HEADER_SIZE: u64 = 48;
+// the indirect buffer holds the draw arguments, then the dispatch arguments
+const DISPATCH_ARGS_OFFSET: u64 = 16;
+const INDIRECT_SIZE: u64 = DISPATCH_ARGS_OFFSET + 12;
+// must match MARKER_VERTICES in region_markers.wgsl
+const MARKER_VERTEX_COUNT: u32 = 2 * 48;
+
+// PopulationState struct that matches alive.wgsl and population.wgsl, the alive flags follow it
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct StateHeader {
+    slots: u32,
+    live: u32,
+    free_count: u32,
+    fill: u32,
+}
+
+// What a region does to the population
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RegionKind {
+    // spawns new boids inside it
+    #[default]
+    Emitter,
+    // removes every boid that enters it
+    Sink,
+}
+
+impl RegionKind {
+    pub const ALL: [RegionKind; 2] = [RegionKind::Emitter, RegionKind::Sink];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RegionKind::Emitter => "emitter",
+            RegionKind::Sink => "sink",
+        }
+    }
+}
+
+// A circle (a ball in 3D) where boids appear or disappear, as edited in the panel and saved in scenario files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Region {
+    pub kind: RegionKind,
+    // z is only used in 3D
+    pub position: [f32; 3],
+    pub radius: f32,
+    // boids spawned per second of simulation time, emitters only
+    pub rate: f32,
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Self {
+            kind: RegionKind::Emitter,
+            position: [0.0; 3],
+            radius: 0.1,
+            rate: 200.0,
+        }
+    }
+}
+
+// Where boids spawned by the spawn button or a resize start out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpawnDistribution {
+    // in a ring (a shell in 3D) around the center, like the boids at startup
+    #[default]
+    Ring,
+    // anywhere in the world
+    Uniform,
+    // packed into a small disc (a ball in 3D) in the middle
+    Cluster,
+}
+
+impl SpawnDistribution {
+    pub const ALL: [SpawnDistribution; 3] = [SpawnDistribution::Ring, SpawnDistribution::Uniform, SpawnDistribution::Cluster];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SpawnDistribution::Ring => "ring",
+            SpawnDistribution::Uniform => "uniform",
+            SpawnDistribution::Cluster => "cluster",
+        }
+    }
+}
+
+// Region struct that matches population.wgsl and region_markers.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuRegion {
+    position: [f32; 3],
+    radius: f32,
+    kind: u32,
+    // boids this emitter spawns this step
+    spawn: u32,
+    // index of its first one among all the boids spawned this step
+    first: u32,
+    _pad: u32,
+}
+
+// RegionList header that matches population.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct RegionHeader {
+    count: u32,
+    // boids spawned this step, the burst first and then each emitter's
+    spawn_total: u32,
+    burst: u32,
+    seed: u32,
+    // vec4s per boid, 1 in 2D and 2 in 3D
+    stride: u32,
+    capacity: u32,
+    num_vertices: u32,
+    // population a resize fills up to
+    resize_to: u32,
+    distribution: u32,
+    _pad: [u32; 3],
+}
+
+// a readback of the state header goes through these steps over a couple of frames
+enum Readback {
+    Idle,
+    Copied,
+    Mapping(mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+// The boids alive in the instance buffers, managed on the GPU. Every slot of the instance buffers
+// has an alive flag: sinks clear it, park the boid far away and push the slot on a free list, emitters pop slots off the free
+// list (or take new ones past the slots in use) and fill them with new boids. The GPU keeps the
+// number of slots in use and writes the draw and dispatch arguments from it, so the CPU never
+// waits for it; the counts shown in the panel are read back a frame or two late.
+pub struct Population {
+    pub regions: Vec<Region>,
+    // boids alive and slots in use, alive or dead, as of the last readback
+    pub live: u32,
+    pub slots: u32,
+    // the header then one alive flag per slot, read by the compute kernel and the grid
+    pub state_buffer: wgpu::Buffer,
+    // draw arguments for the boids, then dispatch arguments covering the slots in use
+    pub indirect_buffer: wgpu::Buffer,
+    region_buffer: wgpu::Buffer,
+    free_list: wgpu::Buffer,
+    // where boids from the spawn button and from growing the population start out
+    pub distribution: SpawnDistribution,
+    // spawn requests from the spawn button
+    burst: u32,
+    // fractions of a boid each emitter owes, carried over to the next step
+    carry: Vec<f32>,
+    seed: u32,
+    stride: u32,
+    capacity: u32,
+    num_vertices: u32,
+    cull_pipeline: wgpu::ComputePipeline,
+    spawn_pipeline: wgpu::ComputePipeline,
+    finalize_pipeline: wgpu::ComputePipeline,
+    resize_begin_pipeline: wgpu::ComputePipeline,
+    resize_count_pipeline: wgpu::ComputePipeline,
+    resize_end_pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // one per instance buffer, writing into the one the step just wrote
+    bind_groups: Vec<wgpu::BindGroup>,
+    readback_buffer: wgpu::Buffer,
+    readback: Readback,
+    marker_pipeline: wgpu::RenderPipeline,
+    marker_bind_group: wgpu::BindGroup,
+}
+
+impl Population {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        space: Space,
+        instance_buffers: &[wgpu::Buffer],
+        capacity: u32,
+        num_vertices: u32,
+    ) -> Self {
+        let (state_buffer, free_list) = create_slot_buffers(device, capacity);
+        let indirect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Population Indirect Buffer"),
+            size: INDIRECT_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let region_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Region Buffer"),
+            size: REGION_HEADER_SIZE + (MAX_REGIONS * std::mem::size_of::<GpuRegion>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = create_readback_buffer(device);
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                storage_entry(0, false),
+                storage_entry(1, false),
+                storage_entry(2, false),
+                storage_entry(3, true),
+                storage_entry(4, false),
+            ],
+            label: Some("population bind group layout"),
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Population Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("population.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("population"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let marker_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("region markers bind group layout"),
+        });
+        let marker_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("region markers bind group"),
+            layout: &marker_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: region_buffer.as_entire_binding() }],
+        });
+        let marker_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Region Markers Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("region_markers.wgsl").into()),
+        });
+        let marker_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Region Markers Pipeline Layout"),
+            bind_group_layouts: &[&marker_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let marker_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Region Markers Pipeline"),
+            layout: Some(&marker_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &marker_shader,
+                entry_point: Some("region_vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &marker_shader,
+                entry_point: Some("region_fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let mut population = Self {
+            regions: Vec::new(),
+            live: 0,
+            slots: 0,
+            state_buffer,
+            indirect_buffer,
+            region_buffer,
+            free_list,
+            distribution: SpawnDistribution::default(),
+            burst: 0,
+            carry: Vec::new(),
+            seed: 0,
+            stride: (space.boid_size() / 16) as u32,
+            capacity,
+            num_vertices,
+            cull_pipeline: pipeline("cull"),
+            spawn_pipeline: pipeline("spawn"),
+            finalize_pipeline: pipeline("finalize"),
+            resize_begin_pipeline: pipeline("resize_begin"),
+            resize_count_pipeline: pipeline("resize_count"),
+            resize_end_pipeline: pipeline("resize_end"),
+            bind_group_layout,
+            bind_groups: Vec::new(),
+            readback_buffer,
+            readback: Readback::Idle,
+            marker_pipeline,
+            marker_bind_group,
+        };
+        population.create_bind_groups(device, instance_buffers);
+        population
+    }
+
+    fn create_bind_groups(&mut self, device: &wgpu::Device, instance_buffers: &[wgpu::Buffer]) {
+        self.bind_groups = instance_buffers.iter().enumerate().map(|(i, boids)| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("population bind group {}", i)),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: boids.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: self.state_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: self.free_list.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 3, resource: self.region_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 4, resource: self.indirect_buffer.as_entire_binding() },
+                ],
+            })
+        }).collect();
+    }
+
+    // Move to new instance buffers with room for `capacity` boids, once the boids in use have been copied
+    // into `instance_buffers[output]`. Slots past `target` are dropped, the alive ones left are counted
+    // on the GPU and new boids from `distribution` make up the difference, ready for the next step
+    #[allow(clippy::too_many_arguments)]
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        instance_buffers: &[wgpu::Buffer],
+        output: usize,
+        capacity: u32,
+        target: u32,
+    ) {
+        let target = target.min(capacity);
+        let (state_buffer, free_list) = create_slot_buffers(device, capacity);
+        let flags = self.capacity.min(capacity) as u64 * 4;
+        encoder.copy_buffer_to_buffer(&self.state_buffer, 0, &state_buffer, 0, STATE_HEADER_SIZE + flags);
+        self.state_buffer = state_buffer;
+        self.free_list = free_list;
+        self.capacity = capacity;
+        self.burst = self.burst.min(capacity);
+        self.create_bind_groups(device, instance_buffers);
+        // counts read back from before the resize would bring back slots the smaller buffers don't have,
+        // so a readback under way is dropped along with its buffer and the next one starts fresh
+        self.slots = self.slots.min(target);
+        self.live = self.live.min(target);
+        self.readback_buffer = create_readback_buffer(device);
+        self.readback = Readback::Idle;
+
+        self.seed = self.seed.wrapping_add(1);
+        self.write_header(queue, &[], 0, target);
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Population Resize Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.bind_groups[output], &[]);
+        compute_pass.set_pipeline(&self.resize_begin_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        compute_pass.set_pipeline(&self.resize_count_pipeline);
+        compute_pass.dispatch_workgroups(capacity.div_ceil(64), 1, 1);
+        compute_pass.set_pipeline(&self.resize_end_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        // at most `target` boids are missing, the kernel knows exactly how many
+        compute_pass.set_pipeline(&self.spawn_pipeline);
+        compute_pass.dispatch_workgroups(target.div_ceil(64), 1, 1);
+        compute_pass.set_pipeline(&self.finalize_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    // header and regions for the population kernels, `spawn_total` boids are spawned by the burst and `regions`
+    fn write_header(&self, queue: &wgpu::Queue, regions: &[GpuRegion], spawn_total: u32, target: u32) {
+        let header = RegionHeader {
+            count: regions.len() as u32,
+            spawn_total,
+            burst: self.burst,
+            seed: self.seed,
+            stride: self.stride,
+            capacity: self.capacity,
+            num_vertices: self.num_vertices,
+            resize_to: target,
+            distribution: self.distribution as u32,
+            _pad: [0; 3],
+        };
+        queue.write_buffer(&self.region_buffer, 0, bytemuck::bytes_of(&header));
+        if !regions.is_empty() {
+            queue.write_buffer(&self.region_buffer, REGION_HEADER_SIZE, bytemuck::cast_slice(regions));
+        }
+    }
+
+    // the state buffer binding of the compute kernel's and the grid's layouts
+    pub fn layout_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: 12,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    // the instance buffers were just filled with `count` boids from the start: all of them alive, nothing free
+    pub fn reset(&mut self, queue: &wgpu::Queue, count: u32) {
+        let count = count.min(self.capacity);
+        let header = StateHeader { slots: count, live: count, free_count: 0, fill: 0 };
+        queue.write_buffer(&self.state_buffer, 0, bytemuck::bytes_of(&header));
+        let mut alive = vec![0u32; self.capacity as usize];
+        alive[..count as usize].fill(1);
+        queue.write_buffer(&self.state_buffer, STATE_HEADER_SIZE, bytemuck::cast_slice(&alive));
+        let args = [self.num_vertices, count, 0, 0, count.div_ceil(64), 1, 1];
+        queue.write_buffer(&self.indirect_buffer, 0, bytemuck::cast_slice(&args));
+        self.burst = 0;
+        self.live = count;
+        self.slots = count;
+    }
+
+    // spawn `count` more boids on the next step, as many as there's room for
+    pub fn request_burst(&mut self, count: u32) {
+        self.burst = (self.burst + count).min(self.capacity);
+    }
+
+    // replace every region, e.g. with the ones from a scenario
+    pub fn set_regions(&mut self, mut regions: Vec<Region>) {
+        regions.truncate(MAX_REGIONS);
+        self.regions = regions;
+    }
+
+    // compute dispatch covering every slot in use, for kernels that run once per boid
+    pub fn dispatch(&self, compute_pass: &mut wgpu::ComputePass) {
+        compute_pass.dispatch_workgroups_indirect(&self.indirect_buffer, DISPATCH_ARGS_OFFSET);
+    }
+
+    // draw the mesh once per slot in use, dead boids are parked where nothing sees them
+    pub fn draw_boids(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.draw_indirect(&self.indirect_buffer, 0);
+    }
+
+    // remove the boids in sinks and spawn new ones into instance buffer `output`, after the step that wrote it
+    pub fn encode_update(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, output: usize, delta_t: f32) {
+        self.carry.resize(self.regions.len(), 0.0);
+        let mut spawn_total = self.burst;
+        let mut regions = Vec::with_capacity(self.regions.len());
+        for (region, carry) in self.regions.iter().take(MAX_REGIONS).zip(&mut self.carry) {
+            let mut spawn = 0;
+            if region.kind == RegionKind::Emitter {
+                *carry += region.rate.max(0.0) * delta_t;
+                spawn = (carry.floor() as u32).min(self.capacity - spawn_total);
+                *carry = carry.fract();
+            }
+            regions.push(GpuRegion {
+                position: region.position,
+                radius: region.radius,
+                kind: region.kind as u32,
+                spawn,
+                first: spawn_total,
+                _pad: 0,
+            });
+            spawn_total += spawn;
+        }
+        self.seed = self.seed.wrapping_add(1);
+        self.write_header(queue, &regions, spawn_total, 0);
+        self.burst = 0;
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Population Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.bind_groups[output], &[]);
+        compute_pass.set_pipeline(&self.cull_pipeline);
+        self.dispatch(&mut compute_pass);
+        if spawn_total > 0 {
+            compute_pass.set_pipeline(&self.spawn_pipeline);
+            compute_pass.dispatch_workgroups(spawn_total.div_ceil(64), 1, 1);
+        }
+        compute_pass.set_pipeline(&self.finalize_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    // copy the counts for the panel, unless the last copy is still being read
+    pub fn encode_readback(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if matches!(self.readback, Readback::Idle) {
+            encoder.copy_buffer_to_buffer(&self.state_buffer, 0, &self.readback_buffer, 0, STATE_HEADER_SIZE);
+            self.readback = Readback::Copied;
+        }
+    }
+
+    // must be called after the encoder holding the readback copy has been submitted
+    pub fn map_readback(&mut self) {
+        if let Readback::Copied = self.readback {
+            let (sender, receiver) = mpsc::channel();
+            self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+            self.readback = Readback::Mapping(receiver);
+        }
+    }
+
+    // take in the counts once they're readable, without blocking the frame
+    pub fn update(&mut self) {
+        let Readback::Mapping(receiver) = &self.readback else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+        };
+        self.readback = Readback::Idle;
+        if result.is_err() {
+            log::warn!("Failed to read back the population counts");
+            return;
+        }
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let header: StateHeader = bytemuck::pod_read_unaligned(&data[..STATE_HEADER_SIZE as usize]);
+            self.slots = header.slots;
+            self.live = header.live;
+        }
+        self.readback_buffer.unmap();
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.regions.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.marker_pipeline);
+        render_pass.set_bind_group(0, &self.marker_bind_group, &[]);
+        render_pass.draw(0..MARKER_VERTEX_COUNT, 0..self.regions.len().min(MAX_REGIONS) as u32);
+    }
+}
+
+// where the slot counts are copied to be read on the CPU
+fn create_readback_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Population Readback Buffer"),
+        size: STATE_HEADER_SIZE,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// the state buffer and free list, both sized for `capacity` slots
+fn create_slot_buffers(device: &wgpu::Device, capacity: u32) -> (wgpu::Buffer, wgpu::Buffer) {
+    let state_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Population State Buffer"),
+        size: STATE_HEADER_SIZE + capacity as u64 * 4,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let free_list = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Population Free List"),
+        size: capacity as u64 * 4,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    (state_buffer, free_list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::{assert_layout, offsets, wgsl_struct};
+
+    #[test]
+    fn regions_match_the_shader() {
+        let fields = offsets!(GpuRegion { position, radius, kind, spawn, first, _pad });
+        assert_layout("region_types.wgsl", "Region", fields, Some(std::mem::size_of::<GpuRegion>()));
+        // the header is the start of RegionList, the regions follow it
+        let fields = offsets!(RegionHeader { count, spawn_total, burst, seed, stride, capacity, num_vertices, resize_to, distribution, _pad as _pad0 });
+        assert_layout("region_types.wgsl", "RegionList", fields, None);
+        assert_eq!(std::mem::size_of::<RegionHeader>() as u64, REGION_HEADER_SIZE);
+        let (members, _) = wgsl_struct("region_types.wgsl", "RegionList");
+        assert!(members.contains(&("regions".to_string(), REGION_HEADER_SIZE as usize)));
+    }
+
+    #[test]
+    fn state_header_matches_both_shaders() {
+        // population.wgsl declares the counters atomic, alive.wgsl only reads them, and the flags follow either way
+        let fields = offsets!(StateHeader { slots, live, free_count, fill });
+        for shader in ["alive.wgsl", "population.wgsl"] {
+            assert_layout(shader, "PopulationState", fields, None);
+            let (members, _) = wgsl_struct(shader, "PopulationState");
+            assert!(members.contains(&("alive".to_string(), STATE_HEADER_SIZE as usize)), "alive flags in {}", shader);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// Adds and removes boids on the GPU, in the instance buffer the step just wrote (see population.rs).
+// cull: boids inside a sink die and their slot goes on the free list
+// spawn: one thread per new boid, reusing a free slot or taking a new one past the slots in use
+// finalize: settles the counters and writes the draw and dispatch arguments for the next step
+// resize_begin, resize_count and resize_end: after the boids were copied into bigger or smaller buffers,
+// drop the slots past the target, count the boids left and rebuild the free list, then spawn fills up the rest
+
+#import region_types.wgsl
+#import math.wgsl
+
+// PopulationState struct that matches StateHeader in population.rs, alive.wgsl reads the same buffer
+struct PopulationState {
+    // slots in use, alive or dead
+    slots: atomic<u32>,
+    live: atomic<u32>,
+    free_count: atomic<u32>,
+    // boids the last resize is short of, spawned before the burst
+    fill: u32,
+    alive: array<u32>,
+}
+
+// must match population.rs
+const SPAWN_UNIFORM : u32 = 1u;
+const SPAWN_CLUSTER : u32 = 2u;
+// dead boids are kept out here, away from the camera, the picking and every neighbor
+const PARKED : f32 = 1.0e6;
+
+// boids as vec4s, `regions.stride` per boid like in grid.wgsl
+@group(0) @binding(0)
+var<storage, read_write> boids: array<vec4<f32>>;
+@group(0) @binding(1)
+var<storage, read_write> state: PopulationState;
+@group(0) @binding(2)
+var<storage, read_write> free_list: array<u32>;
+@group(0) @binding(3)
+var<storage, read> regions: RegionList;
+// draw arguments, then dispatch arguments
+@group(0) @binding(4)
+var<storage, read_write> indirect: array<u32>;
+
+var<private> rng: u32;
+
+// next value in [0, 1) of this thread's PCG sequence
+fn random() -> f32 {
+    rng = rng * 747796405u + 2891336453u;
+    var x = ((rng >> ((rng >> 28u) + 4u)) ^ rng) * 277803737u;
+    x = (x >> 22u) ^ x;
+    return f32(x) / 4294967296.0;
+}
+
+// random unit vector, in the xy plane unless `volume`
+fn random_direction(volume: bool) -> vec3<f32> {
+    let theta = random() * TAU;
+    if (!volume) {
+        return vec3<f32>(cos(theta), sin(theta), 0.0);
+    }
+    let z = random() * 2.0 - 1.0;
+    let r = sqrt(1.0 - z * z);
+    return vec3<f32>(r * cos(theta), r * sin(theta), z);
+}
+
+// spread evenly over a disc (a ball in 3D) of radius 1
+fn random_in_ball(volume: bool) -> vec3<f32> {
+    return random_direction(volume) * pow(random(), select(0.5, 1.0 / 3.0, volume));
+}
+
+// where a boid from the burst or a resize starts out
+fn burst_position(volume: bool) -> vec3<f32> {
+    switch regions.distribution {
+        case SPAWN_UNIFORM: {
+            return vec3<f32>(random() * 2.0 - 1.0, random() * 2.0 - 1.0, select(0.0, random() * 2.0 - 1.0, volume));
+        }
+        case SPAWN_CLUSTER: {
+            return random_in_ball(volume) * 0.15;
+        }
+        default: {
+            return random_direction(volume) * mix(0.1, 0.7, random());
+        }
+    }
+}
+
+fn is_volume() -> bool {
+    return regions.stride > 1u;
+}
+
+fn boid_pos(i: u32) -> vec3<f32> {
+    let v = boids[i * regions.stride];
+    return select(vec3<f32>(v.xy, 0.0), v.xyz, is_volume());
+}
+
+fn region_pos(region: Region) -> vec3<f32> {
+    return select(vec3<f32>(region.position.xy, 0.0), region.position, is_volume());
+}
+
+fn write_boid(i: u32, pos: vec3<f32>, vel: vec3<f32>) {
+    if (is_volume()) {
+        boids[i * 2u] = vec4<f32>(pos, 0.0);
+        boids[i * 2u + 1u] = vec4<f32>(vel, 0.0);
+    } else {
+        boids[i] = vec4<f32>(pos.xy, vel.xy);
+    }
+}
+
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= atomicLoad(&state.slots) || state.alive[i] == 0u) {
+        return;
+    }
+    let pos = boid_pos(i);
+    for (var r = 0u; r < regions.count; r++) {
+        let region = regions.regions[r];
+        if (region.kind == REGION_SINK && distance(pos, region_pos(region)) < region.radius) {
+            state.alive[i] = 0u;
+            write_boid(i, vec3<f32>(PARKED), vec3<f32>(0.0));
+            free_list[atomicAdd(&state.free_count, 1u)] = i;
+            atomicSub(&state.live, 1u);
+            return;
+        }
+    }
+}
+
+@compute @workgroup_size(64)
+fn spawn(@builtin(global_invocation_id) id: vec3<u32>) {
+    let n = id.x;
+    let fill = state.fill;
+    if (n >= fill + regions.spawn_total) {
+        return;
+    }
+    // the free count wraps below zero once the list runs out, finalize puts it back to 0
+    var slot: u32;
+    let free = atomicSub(&state.free_count, 1u);
+    if (free > 0u && free <= regions.capacity) {
+        slot = free_list[free - 1u];
+    } else {
+        slot = atomicAdd(&state.slots, 1u);
+        if (slot >= regions.capacity) {
+            return;
+        }
+    }
+
+    rng = (n * 0x9e3779b9u) ^ (regions.seed * 0x85ebca6bu);
+    let volume = is_volume();
+    // the boids filling up a resize come first, then the burst, then each emitter's
+    let k = select(n - fill, 0u, n < fill);
+    var pos: vec3<f32>;
+    if (n < fill || k < regions.burst) {
+        pos = burst_position(volume);
+    } else {
+        for (var r = 0u; r < regions.count; r++) {
+            let region = regions.regions[r];
+            if (k >= region.first && k < region.first + region.spawn) {
+                pos = region_pos(region) + random_in_ball(volume) * region.radius;
+                break;
+            }
+        }
+    }
+    let vel = random_direction(volume) * mix(0.005, 0.015, random());
+    write_boid(slot, pos, vel);
+    state.alive[slot] = 1u;
+    atomicAdd(&state.live, 1u);
+}
+
+@compute @workgroup_size(1)
+fn finalize() {
+    let slots = min(atomicLoad(&state.slots), regions.capacity);
+    atomicStore(&state.slots, slots);
+    if (atomicLoad(&state.free_count) > regions.capacity) {
+        atomicStore(&state.free_count, 0u);
+    }
+    state.fill = 0u;
+    indirect[0] = regions.num_vertices;
+    indirect[1] = slots;
+    indirect[2] = 0u;
+    indirect[3] = 0u;
+    indirect[4] = (slots + 63u) / 64u;
+    indirect[5] = 1u;
+    indirect[6] = 1u;
+}
+
+@compute @workgroup_size(1)
+fn resize_begin() {
+    atomicStore(&state.slots, min(atomicLoad(&state.slots), min(regions.resize_to, regions.capacity)));
+    atomicStore(&state.live, 0u);
+    atomicStore(&state.free_count, 0u);
+}
+
+@compute @workgroup_size(64)
+fn resize_count(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= regions.capacity) {
+        return;
+    }
+    if (i >= atomicLoad(&state.slots)) {
+        state.alive[i] = 0u;
+    } else if (state.alive[i] != 0u) {
+        atomicAdd(&state.live, 1u);
+    } else {
+        free_list[atomicAdd(&state.free_count, 1u)] = i;
+    }
+}
+
+@compute @workgroup_size(1)
+fn resize_end() {
+    // every boid left is in a slot below the target, so there are never more of them than it
+    state.fill = regions.resize_to - atomicLoad(&state.live);
+}
@@ -0,0 +1,432 @@
+use crate::shaders;
+
+// the scene is drawn in linear HDR so bloom and trails can go above 1.0 before tone mapping
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// depth buffer that goes with the scene texture, used by the 3D mode
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+// every pass gets its own uniform slot, bound with a dynamic offset
+const UNIFORM_SLOT_SIZE: u64 = 256;
+const MAX_UNIFORM_SLOTS: u64 = 32;
+// render targets are kept per output size (window, screenshots, recordings), least recently used go first
+const MAX_CACHED_TARGETS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffect {
+    // fading accumulation of previous frames, for motion-blur style trails
+    Trails { persistence: f32 },
+    Bloom { threshold: f32, intensity: f32, radius: f32 },
+    ToneMap { exposure: f32 },
+    Vignette { strength: f32, radius: f32 },
+}
+
+impl PostEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostEffect::Trails { .. } => "trails",
+            PostEffect::Bloom { .. } => "bloom",
+            PostEffect::ToneMap { .. } => "tonemap",
+            PostEffect::Vignette { .. } => "vignette",
+        }
+    }
+
+    // the effect with its default settings, by the name used in `PostConfig::from_spec`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "trails" => PostEffect::Trails { persistence: 0.9 },
+            "bloom" => PostEffect::Bloom { threshold: 0.6, intensity: 1.0, radius: 1.5 },
+            "tonemap" => PostEffect::ToneMap { exposure: 1.0 },
+            "vignette" => PostEffect::Vignette { strength: 0.6, radius: 0.4 },
+            _ => return None,
+        })
+    }
+
+    // the main parameter of the effect, the one a spec like "trails=0.95" sets
+    pub fn amount_mut(&mut self) -> &mut f32 {
+        match self {
+            PostEffect::Trails { persistence } => persistence,
+            PostEffect::Bloom { intensity, .. } => intensity,
+            PostEffect::ToneMap { exposure } => exposure,
+            PostEffect::Vignette { strength, .. } => strength,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostStage {
+    pub effect: PostEffect,
+    pub enabled: bool,
+}
+
+// The post-processing chain, stages run in order and disabled ones are skipped
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostConfig {
+    pub stages: Vec<PostStage>,
+}
+
+impl Default for PostConfig {
+    // every effect in its usual order, all off so the plain scene is shown
+    fn default() -> Self {
+        Self {
+            stages: ["trails", "bloom", "tonemap", "vignette"]
+                .iter()
+                .map(|name| PostStage {
+                    effect: PostEffect::from_name(name).unwrap(),
+                    enabled: false,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl PostConfig {
+    // parse a comma separated list like "trails=0.95,bloom,tonemap=1.2".
+    // Listed effects are enabled in that order, the rest are kept disabled after them.
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let mut stages = Vec::new();
+        for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (name, amount) = match item.split_once('=') {
+                Some((name, amount)) => (name, Some(amount)),
+                None => (item, None),
+            };
+            let mut effect = PostEffect::from_name(name).ok_or_else(|| format!("unknown post effect {}", name))?;
+            if let Some(amount) = amount {
+                *effect.amount_mut() = amount.parse().map_err(|_| format!("invalid amount for {}", name))?;
+            }
+            stages.push(PostStage { effect, enabled: true });
+        }
+        for stage in Self::default().stages {
+            if !stages.iter().any(|s| s.effect.name() == stage.effect.name()) {
+                stages.push(stage);
+            }
+        }
+        Ok(Self { stages })
+    }
+}
+
+// PostUniforms struct that matches postprocess.wgsl
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PostUniforms {
+    pub(crate) strength: f32,
+    pub(crate) threshold: f32,
+    pub(crate) radius: f32,
+    pub(crate) _pad: f32,
+    pub(crate) texel: [f32; 2],
+    pub(crate) direction: [f32; 2],
+}
+
+// intermediate textures for one output size
+struct PostTargets {
+    width: u32,
+    height: u32,
+    scene: wgpu::TextureView,
+    depth: wgpu::TextureView,
+    ping: [wgpu::TextureView; 2],
+    // trails read last frame's result from one and write the new one into the other
+    history: [wgpu::TextureView; 2],
+    history_index: usize,
+    // simulation step the latest history was written for, drawing the same step again
+    // (while paused, or for a screenshot) redoes that frame instead of fading it twice
+    history_step: Option<usize>,
+    // half resolution, for the bloom bright pass and blur
+    bloom: [wgpu::TextureView; 2],
+    last_used: u64,
+}
+
+impl PostTargets {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let create_with_format = |label: &str, width: u32, height: u32, format: wgpu::TextureFormat| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }).create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let create = |label: &str, width: u32, height: u32| create_with_format(label, width, height, HDR_FORMAT);
+        let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+        Self {
+            width,
+            height,
+            scene: create("Post Scene Texture", width, height),
+            depth: create_with_format("Post Depth Texture", width, height, DEPTH_FORMAT),
+            ping: [create("Post Ping Texture", width, height), create("Post Pong Texture", width, height)],
+            history: [create("Post History Texture", width, height), create("Post History Texture", width, height)],
+            history_index: 0,
+            history_step: None,
+            bloom: [create("Post Bloom Texture", half_width, half_height), create("Post Bloom Texture", half_width, half_height)],
+            last_used: 0,
+        }
+    }
+}
+
+// Runs the post-processing chain from the HDR scene texture into the final output
+pub struct PostProcessor {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    blit_pipeline: wgpu::RenderPipeline,
+    trails_pipeline: wgpu::RenderPipeline,
+    bright_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    tonemap_pipeline: wgpu::RenderPipeline,
+    vignette_pipeline: wgpu::RenderPipeline,
+    targets: Vec<PostTargets>,
+    frame: u64,
+}
+
+impl PostProcessor {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::builtin("postprocess.wgsl").into()),
+        });
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<PostUniforms>() as _),
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("post bind group layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point: &str, format: wgpu::TextureFormat| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("fullscreen_vs"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        Self {
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Post Sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+            uniform_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Post Uniform Buffer"),
+                size: UNIFORM_SLOT_SIZE * MAX_UNIFORM_SLOTS,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            blit_pipeline: pipeline("blit_fs", output_format),
+            trails_pipeline: pipeline("trails_fs", HDR_FORMAT),
+            bright_pipeline: pipeline("bright_fs", HDR_FORMAT),
+            blur_pipeline: pipeline("blur_fs", HDR_FORMAT),
+            composite_pipeline: pipeline("bloom_composite_fs", HDR_FORMAT),
+            tonemap_pipeline: pipeline("tonemap_fs", HDR_FORMAT),
+            vignette_pipeline: pipeline("vignette_fs", HDR_FORMAT),
+            bind_group_layout,
+            targets: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    // the HDR texture the scene has to be drawn into before `run` is called for the same size,
+    // and a depth texture of the same size
+    pub fn scene_views(&mut self, device: &wgpu::Device, width: u32, height: u32) -> (wgpu::TextureView, wgpu::TextureView) {
+        let targets = self.targets(device, width, height);
+        (targets.scene.clone(), targets.depth.clone())
+    }
+
+    fn targets(&mut self, device: &wgpu::Device, width: u32, height: u32) -> &mut PostTargets {
+        self.frame += 1;
+        let index = match self.targets.iter().position(|t| t.width == width && t.height == height) {
+            Some(index) => index,
+            None => {
+                if self.targets.len() >= MAX_CACHED_TARGETS {
+                    let oldest = (0..self.targets.len()).min_by_key(|&i| self.targets[i].last_used).unwrap();
+                    self.targets.swap_remove(oldest);
+                }
+                self.targets.push(PostTargets::new(device, width, height));
+                self.targets.len() - 1
+            }
+        };
+        let targets = &mut self.targets[index];
+        targets.last_used = self.frame;
+        targets
+    }
+
+    // apply the enabled stages to the scene texture of this size and write the result to `output`.
+    // `step` is the simulation step being drawn, trails only advance when it changes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn run(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        config: &PostConfig,
+        output: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        step: usize,
+    ) {
+        let targets = self.targets(device, width, height);
+        let scene = targets.scene.clone();
+        let ping = targets.ping.clone();
+        let history = targets.history.clone();
+        let bloom = targets.bloom.clone();
+        let mut history_index = targets.history_index;
+        if targets.history_step == Some(step) {
+            history_index = 1 - history_index;
+        }
+        let half_texel = [1.0 / (width / 2).max(1) as f32, 1.0 / (height / 2).max(1) as f32];
+
+        let mut slot = 0;
+        let mut ping_index = 0;
+        let mut current = scene;
+        for stage in config.stages.iter().filter(|stage| stage.enabled) {
+            match stage.effect {
+                PostEffect::Trails { persistence } => {
+                    // the output of this stage becomes next frame's history
+                    let write = 1 - history_index;
+                    let uniforms = PostUniforms { strength: persistence, ..Default::default() };
+                    self.pass(device, queue, encoder, &mut slot, &self.trails_pipeline, &current, &history[history_index], uniforms, &history[write]);
+                    history_index = write;
+                    current = history[write].clone();
+                }
+                PostEffect::Bloom { threshold, intensity, radius } => {
+                    let bright = PostUniforms { threshold, ..Default::default() };
+                    self.pass(device, queue, encoder, &mut slot, &self.bright_pipeline, &current, &current, bright, &bloom[0]);
+                    let horizontal = PostUniforms { radius, texel: half_texel, direction: [1.0, 0.0], ..Default::default() };
+                    self.pass(device, queue, encoder, &mut slot, &self.blur_pipeline, &bloom[0], &bloom[0], horizontal, &bloom[1]);
+                    let vertical = PostUniforms { radius, texel: half_texel, direction: [0.0, 1.0], ..Default::default() };
+                    self.pass(device, queue, encoder, &mut slot, &self.blur_pipeline, &bloom[1], &bloom[1], vertical, &bloom[0]);
+                    let composite = PostUniforms { strength: intensity, ..Default::default() };
+                    self.pass(device, queue, encoder, &mut slot, &self.composite_pipeline, &current, &bloom[0], composite, &ping[ping_index]);
+                    current = ping[ping_index].clone();
+                    ping_index = 1 - ping_index;
+                }
+                PostEffect::ToneMap { exposure } => {
+                    let uniforms = PostUniforms { strength: exposure, ..Default::default() };
+                    self.pass(device, queue, encoder, &mut slot, &self.tonemap_pipeline, &current, &current, uniforms, &ping[ping_index]);
+                    current = ping[ping_index].clone();
+                    ping_index = 1 - ping_index;
+                }
+                PostEffect::Vignette { strength, radius } => {
+                    let uniforms = PostUniforms { strength, radius, ..Default::default() };
+                    self.pass(device, queue, encoder, &mut slot, &self.vignette_pipeline, &current, &current, uniforms, &ping[ping_index]);
+                    current = ping[ping_index].clone();
+                    ping_index = 1 - ping_index;
+                }
+            }
+        }
+        self.pass(device, queue, encoder, &mut slot, &self.blit_pipeline, &current, &current, PostUniforms::default(), output);
+        let targets = self.targets(device, width, height);
+        targets.history_index = history_index;
+        targets.history_step = Some(step);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn pass(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        slot: &mut u64,
+        pipeline: &wgpu::RenderPipeline,
+        input: &wgpu::TextureView,
+        second: &wgpu::TextureView,
+        uniforms: PostUniforms,
+        output: &wgpu::TextureView,
+    ) {
+        if *slot >= MAX_UNIFORM_SLOTS {
+            log::warn!("Post-processing chain is too long, skipping the remaining passes");
+            return;
+        }
+        let offset = *slot * UNIFORM_SLOT_SIZE;
+        *slot += 1;
+        queue.write_buffer(&self.uniform_buffer, offset, bytemuck::bytes_of(&uniforms));
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(input) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(second) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &self.uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<PostUniforms>() as _),
+                    }),
+                },
+            ],
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[offset as u32]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
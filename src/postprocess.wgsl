@@ -0,0 +1,103 @@
+// Post-processing shaders, every pass draws one fullscreen triangle
+
+// PostUniforms struct that matches the uniform slots written in postprocess.rs
+struct PostUniforms {
+    // meaning depends on the pass, see the entry points below
+    strength: f32,
+    threshold: f32,
+    radius: f32,
+    _pad: f32,
+    // size of one texel of the input texture, and the blur direction in texels
+    texel: vec2<f32>,
+    direction: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+// history for trails, blurred highlights for the bloom composite, unused otherwise
+@group(0) @binding(1)
+var second_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var linear_sampler: sampler;
+@group(0) @binding(3)
+var<uniform> post: PostUniforms;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// a triangle covering the whole screen, no vertex buffer needed
+@vertex
+fn fullscreen_vs(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// copy into the output format, the surface takes care of sRGB encoding
+@fragment
+fn blit_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(input_texture, linear_sampler, in.uv).rgb, 1.0);
+}
+
+// trails: keep the brighter of the new frame and the faded history.
+// strength = how much of the history survives each frame
+@fragment
+fn trails_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let current = textureSample(input_texture, linear_sampler, in.uv).rgb;
+    let history = textureSample(second_texture, linear_sampler, in.uv).rgb;
+    return vec4<f32>(max(current, history * post.strength), 1.0);
+}
+
+// bloom step 1: keep what is brighter than threshold, rendered at half resolution
+@fragment
+fn bright_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, linear_sampler, in.uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let weight = max(brightness - post.threshold, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(color * weight, 1.0);
+}
+
+// bloom step 2: separable 9 tap gaussian, run once horizontally and once vertically.
+// radius scales the distance between taps
+@fragment
+fn blur_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    let step = post.texel * post.direction * post.radius;
+    var color = textureSample(input_texture, linear_sampler, in.uv).rgb * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = step * f32(i);
+        color += textureSample(input_texture, linear_sampler, in.uv + offset).rgb * weights[i];
+        color += textureSample(input_texture, linear_sampler, in.uv - offset).rgb * weights[i];
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+// bloom step 3: add the blurred highlights back, strength = bloom intensity
+@fragment
+fn bloom_composite_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, linear_sampler, in.uv).rgb;
+    let bloom = textureSample(second_texture, linear_sampler, in.uv).rgb;
+    return vec4<f32>(color + bloom * post.strength, 1.0);
+}
+
+// ACES filmic curve (Narkowicz fit), strength = exposure
+@fragment
+fn tonemap_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let x = textureSample(input_texture, linear_sampler, in.uv).rgb * post.strength;
+    let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
+
+// darken towards the corners, strength = how dark, radius = where the falloff starts
+@fragment
+fn vignette_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, linear_sampler, in.uv).rgb;
+    // 0 at the center, 1 at the corners
+    let dist = length(in.uv - vec2<f32>(0.5)) / 0.70710678;
+    let falloff = smoothstep(post.radius, 1.0, dist);
+    return vec4<f32>(color * (1.0 - falloff * post.strength), 1.0);
+}
@@ -0,0 +1,35 @@
+// Stateless random numbers and the wander steering, imported by compute.wgsl and compute3d.wgsl.
+// Everything is a hash of the boid, the step and `params.seed`, so a run with the same seed wanders the same way
+
+// PCG hash of `n`
+fn pcg(n: u32) -> u32 {
+    let state = n * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// hash of `n` in [0, 1)
+fn hash01(n: u32) -> f32 {
+    return f32(pcg(n)) / 4294967296.0;
+}
+
+// value in [-1, 1] that changes smoothly with `t`, different for every `key`
+fn value_noise(key: u32, t: f32) -> f32 {
+    let step = u32(floor(t));
+    let a = hash01(key + step);
+    let b = hash01(key + step + 1u);
+    return mix(a, b, smoothstep(0.0, 1.0, fract(t))) * 2.0 - 1.0;
+}
+
+// random steering of boid `i`, drifting smoothly between directions `params.wander_rate` times a second.
+// `volume` lets it leave the xy plane
+fn wander_steering(i: u32, volume: bool) -> vec3<f32> {
+    if (params.wander == 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let t = f32(params.step) * params.delta_t * params.wander_rate;
+    // a separate noise per axis, keyed by the seed, the boid and the axis
+    let key = pcg(params.seed ^ pcg(i)) * 3u;
+    let drift = vec3<f32>(value_noise(pcg(key), t), value_noise(pcg(key + 1u), t), select(0.0, value_noise(pcg(key + 2u), t), volume));
+    return drift * params.wander;
+}
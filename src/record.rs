@@ -0,0 +1,155 @@
+use std::{fs::{self, File}, io::{self, BufWriter, Write}, path::PathBuf};
+
+use crate::{capture::{write_png, OffscreenTarget}, sim::Simulation};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+    // one numbered PNG per frame inside the output directory
+    PngSequence,
+    // a single uncompressed YUV4MPEG2 stream that ffmpeg and most players read directly
+    Y4m,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordOptions {
+    pub width: u32,
+    pub height: u32,
+    // frame rate written into the video, it doesn't affect how fast the recording is made
+    pub fps: u32,
+    // simulation steps between two recorded frames
+    pub steps_per_frame: u32,
+    pub format: RecordFormat,
+    // a directory for PNG sequences, a file for Y4M
+    pub output: PathBuf,
+}
+
+impl RecordOptions {
+    // PNG sequence into `output`, unless it ends in .y4m
+    pub fn new(output: PathBuf, width: u32, height: u32) -> Self {
+        let format = match output.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("y4m") => RecordFormat::Y4m,
+            _ => RecordFormat::PngSequence,
+        };
+        Self {
+            width,
+            height,
+            fps: 60,
+            steps_per_frame: 1,
+            format,
+            output,
+        }
+    }
+}
+
+enum FrameSink {
+    Png,
+    Y4m(BufWriter<File>),
+}
+
+// Writes simulation frames rendered offscreen at a fixed size, one per call to `capture`.
+// Each capture waits for the GPU, so frames are exact however long they take to render.
+pub struct Recorder {
+    pub options: RecordOptions,
+    pub frames_written: u32,
+    target: OffscreenTarget,
+    sink: FrameSink,
+}
+
+impl Recorder {
+    pub fn new(sim: &Simulation, options: RecordOptions) -> io::Result<Self> {
+        let target = OffscreenTarget::new(&sim.device, options.width, options.height, sim.format)?;
+        let sink = match options.format {
+            RecordFormat::PngSequence => {
+                fs::create_dir_all(&options.output)?;
+                FrameSink::Png
+            }
+            RecordFormat::Y4m => {
+                let mut writer = BufWriter::new(File::create(&options.output)?);
+                // full resolution chroma (C444) so no subsampling is needed
+                writeln!(writer, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", options.width, options.height, options.fps)?;
+                FrameSink::Y4m(writer)
+            }
+        };
+        Ok(Self {
+            options,
+            frames_written: 0,
+            target,
+            sink,
+        })
+    }
+
+    // draw the simulation's current boids and append them as the next frame
+    pub fn capture(&mut self, sim: &mut Simulation) -> io::Result<()> {
+        let mut encoder = sim.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Record Encoder"),
+        });
+        sim.draw_scene(&mut encoder, &self.target.view, self.options.width, self.options.height);
+        sim.queue.submit(std::iter::once(encoder.finish()));
+        let pixels = self.target.read_rgba(&sim.device, &sim.queue)?;
+
+        match &mut self.sink {
+            FrameSink::Png => {
+                let path = self.options.output.join(format!("frame_{:05}.png", self.frames_written));
+                write_png(&path, self.options.width, self.options.height, &pixels)?;
+            }
+            FrameSink::Y4m(writer) => {
+                writer.write_all(b"FRAME\n")?;
+                write_yuv444(writer, &pixels)?;
+            }
+        }
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        if let FrameSink::Y4m(mut writer) = self.sink {
+            writer.flush()?;
+        }
+        log::info!("Recorded {} frames to {}", self.frames_written, self.options.output.display());
+        Ok(())
+    }
+}
+
+// planar Y, U, V from sRGB encoded RGBA pixels, BT.601 limited range which is what Y4M readers assume
+fn write_yuv444(writer: &mut impl Write, rgba: &[u8]) -> io::Result<()> {
+    let pixel_count = rgba.len() / 4;
+    let mut planes = vec![0u8; pixel_count * 3];
+    for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+        let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+        let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+        let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+        planes[i] = y.round() as u8;
+        planes[pixel_count + i] = u.round() as u8;
+        planes[2 * pixel_count + i] = v.round() as u8;
+    }
+    writer.write_all(&planes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_convert_to_limited_range_planes() {
+        // black, white, red, green and blue, alpha is ignored
+        let rgba = [0, 0, 0, 255, 255, 255, 255, 0, 255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255];
+        let mut planes = Vec::new();
+        write_yuv444(&mut planes, &rgba).unwrap();
+        assert_eq!(planes, [
+            16, 235, 81, 145, 41, // Y
+            128, 128, 90, 54, 240, // U
+            128, 128, 240, 34, 110, // V
+        ]);
+    }
+
+    #[test]
+    fn the_extension_picks_the_format() {
+        let format = |output: &str| RecordOptions::new(PathBuf::from(output), 640, 480).format;
+        assert_eq!(format("flock.y4m"), RecordFormat::Y4m);
+        assert_eq!(format("runs/flock.Y4M"), RecordFormat::Y4m);
+        assert_eq!(format("frames"), RecordFormat::PngSequence);
+        assert_eq!(format("frames.png"), RecordFormat::PngSequence);
+        assert_eq!(format("flock.y4m.d"), RecordFormat::PngSequence);
+    }
+}
@@ -0,0 +1,341 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::{boid::Boid, params::{SimParams, FALLOFF_INVERSE_SQUARE, FALLOFF_LINEAR, FALLOFF_SMOOTHSTEP, INTEGRATOR_EXPLICIT_EULER, INTEGRATOR_RK2, INTEGRATOR_VELOCITY_VERLET, MAX_K_NEIGHBORS, NEIGHBORS_TOPOLOGICAL}};
+
+// CPU version of a 2D step of compute.wgsl and flight.wgsl with the Reynolds model: the three rules with their falloffs,
+// field of view and both neighbor modes, the flight limits and every integrator. It leaves out flow fields,
+// force sources, paths, leaders, wander and emitters and sinks, and looks at every boid for each neighbor search,
+// so it's for checking the kernel and studying the integrators on small flocks, not for running them
+
+// must match flight.wgsl
+const STEERING_RATE: f32 = 1.0 / 0.03;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Vec2 {
+    x: f32,
+    y: f32,
+}
+
+impl Vec2 {
+    const ZERO: Self = Self { x: 0.0, y: 0.0 };
+
+    fn new(v: [f32; 2]) -> Self {
+        Self { x: v[0], y: v[1] }
+    }
+
+    fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    // like safe_normalize in flight.wgsl
+    fn normalize_or(self, fallback: Self) -> Self {
+        let len = self.length();
+        if len < 1.0e-12 { fallback } else { self * (1.0 / len) }
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self { x: self.x - other.x, y: self.y - other.y }
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Self;
+    fn mul(self, s: f32) -> Self {
+        Self { x: self.x * s, y: self.y * s }
+    }
+}
+
+// PCG hash, like pcg in random.wgsl
+fn pcg(n: u32) -> u32 {
+    let state = n.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+fn hash01(n: u32) -> f32 {
+    pcg(n) as f32 / 4294967296.0
+}
+
+fn falloff(kind: u32, dist: f32, radius: f32) -> f32 {
+    if dist > radius || radius <= 0.0 {
+        return 0.0;
+    }
+    let x = dist / radius;
+    match kind {
+        FALLOFF_LINEAR => 1.0 - x,
+        FALLOFF_SMOOTHSTEP => 1.0 - x * x * (3.0 - 2.0 * x),
+        FALLOFF_INVERSE_SQUARE => (1.0 / (x * x).max(0.01) - 1.0) / 99.0,
+        _ => 1.0,
+    }
+}
+
+fn view_cos(pos: Vec2, vel: Vec2, other_pos: Vec2, dist: f32) -> f32 {
+    let heading_len = vel.length();
+    if heading_len == 0.0 || dist == 0.0 {
+        return 1.0;
+    }
+    (vel.dot(other_pos - pos) / (heading_len * dist)).clamp(-1.0, 1.0)
+}
+
+// One step of the flock the same way the 2D kernel takes it
+pub fn step(boids: &[Boid], params: &SimParams) -> Vec<Boid> {
+    let blind_cos = -(params.blind_spot * 0.5).cos();
+    (0..boids.len()).map(|i| {
+        let pos = Vec2::new(boids[i].pos);
+        let vel = Vec2::new(boids[i].vel);
+        // the neighbors are picked once, at the start of the step
+        let neighbors = if params.neighbor_mode == NEIGHBORS_TOPOLOGICAL {
+            nearest(boids, i, params.k_neighbors.clamp(1, MAX_K_NEIGHBORS) as usize, blind_cos)
+        } else {
+            (0..boids.len()).collect()
+        };
+        let steering = |at: Vec2, at_vel: Vec2, ahead: f32| steering(boids, &neighbors, i, at, at_vel, ahead, params);
+
+        let dt = params.delta_t;
+        let acc = steering(pos, vel, 0.0);
+        let (mut new_pos, new_vel) = match params.integrator {
+            INTEGRATOR_EXPLICIT_EULER => (pos + vel * dt, fly(i, vel, acc, dt, params)),
+            INTEGRATOR_VELOCITY_VERLET => {
+                let new_pos = pos + vel * dt + acc * (0.5 * dt * dt);
+                let new_acc = steering(new_pos, vel, dt);
+                (new_pos, fly(i, vel, (acc + new_acc) * 0.5, dt, params))
+            }
+            INTEGRATOR_RK2 => {
+                let mid_vel = fly(i, vel, acc, dt * 0.5, params);
+                let mid_acc = steering(pos + vel * (dt * 0.5), mid_vel, dt * 0.5);
+                (pos + mid_vel * dt, fly(i, vel, mid_acc, dt, params))
+            }
+            _ => {
+                let new_vel = fly(i, vel, acc, dt, params);
+                (pos + new_vel * dt, new_vel)
+            }
+        };
+
+        // limit by the screen
+        for v in [&mut new_pos.x, &mut new_pos.y] {
+            if *v > 1.0 {
+                *v = -1.0;
+            } else if *v < -1.0 {
+                *v = 1.0;
+            }
+        }
+        Boid { pos: [new_pos.x, new_pos.y], vel: [new_vel.x, new_vel.y] }
+    }).collect()
+}
+
+// the `k` boids closest to boid `i` outside its blind spot, closest first
+fn nearest(boids: &[Boid], i: usize, k: usize, blind_cos: f32) -> Vec<usize> {
+    let pos = Vec2::new(boids[i].pos);
+    let vel = Vec2::new(boids[i].vel);
+    let mut found: Vec<(f32, usize)> = boids.iter().enumerate()
+        .filter(|&(j, _)| j != i)
+        .map(|(j, other)| ((Vec2::new(other.pos) - pos).length(), j))
+        .filter(|&(dist, j)| blind_cos <= -1.0 || view_cos(pos, vel, Vec2::new(boids[j].pos), dist) >= blind_cos)
+        .collect();
+    found.sort_by(|a, b| a.0.total_cmp(&b.0));
+    found.into_iter().take(k).map(|(_, j)| j).collect()
+}
+
+// acceleration of boid `i` at `pos` with `vel` when its neighbors have flown on for `ahead` seconds
+fn steering(boids: &[Boid], neighbors: &[usize], i: usize, pos: Vec2, vel: Vec2, ahead: f32, params: &SimParams) -> Vec2 {
+    let topological = params.neighbor_mode == NEIGHBORS_TOPOLOGICAL;
+    let perception = params.separation_distance.max(params.alignment_distance.max(params.cohesion_distance));
+    let fov_cos = (params.fov * 0.5).cos();
+    let blind_cos = -(params.blind_spot * 0.5).cos();
+    let (mut alignment_total, mut cohesion_total) = (0.0, 0.0);
+    let (mut avg_alignment, mut avg_cohesion, mut avg_separation) = (Vec2::ZERO, Vec2::ZERO, Vec2::ZERO);
+
+    for &j in neighbors {
+        if i == j {
+            continue;
+        }
+        let other_vel = Vec2::new(boids[j].vel);
+        let other_pos = Vec2::new(boids[j].pos) + other_vel * ahead;
+        let dist = (other_pos - pos).length();
+        if !topological && dist > perception {
+            continue;
+        }
+        let cos_angle = view_cos(pos, vel, other_pos, dist);
+        if params.blind_spot > 0.0 && cos_angle < blind_cos {
+            continue;
+        }
+        let separation = falloff(params.separation_falloff, dist, params.separation_distance);
+        if separation > 0.0 && dist != 0.0 {
+            avg_separation = avg_separation - (other_pos - pos) * separation;
+        }
+        if params.fov < std::f32::consts::TAU && cos_angle < fov_cos {
+            continue;
+        }
+        let (alignment, cohesion) = if topological {
+            (1.0, 1.0)
+        } else {
+            (falloff(params.alignment_falloff, dist, params.alignment_distance), falloff(params.cohesion_falloff, dist, params.cohesion_distance))
+        };
+        avg_alignment = avg_alignment + other_vel * alignment;
+        alignment_total += alignment;
+        avg_cohesion = avg_cohesion + other_pos * cohesion;
+        cohesion_total += cohesion;
+    }
+    if alignment_total > 0.0 {
+        avg_alignment = (avg_alignment * (1.0 / alignment_total)).normalize_or(Vec2::ZERO);
+    }
+    if cohesion_total > 0.0 {
+        avg_cohesion = (avg_cohesion * (1.0 / cohesion_total)).normalize_or(Vec2::ZERO) - pos;
+    }
+    let acc = (avg_cohesion * params.cohesion_weight + avg_alignment * params.alignment_weight
+        + avg_separation * params.separation_weight) * STEERING_RATE;
+    // the strongest acceleration a boid manages
+    if params.max_force > 0.0 && acc.length() > params.max_force {
+        acc.normalize_or(Vec2::ZERO) * params.max_force
+    } else {
+        acc
+    }
+}
+
+// like fly in flight.wgsl
+fn fly(i: usize, vel: Vec2, acc: Vec2, dt: f32, params: &SimParams) -> Vec2 {
+    let desired = vel * (-params.drag * dt).exp() + acc * dt;
+    let rest = {
+        let theta = hash01(pcg(i as u32 ^ params.seed)) * std::f32::consts::TAU;
+        Vec2 { x: theta.cos(), y: theta.sin() }
+    };
+    let heading = vel.normalize_or(desired.normalize_or(rest));
+    let wanted = desired.normalize_or(heading);
+    let max_angle = if params.max_turn_rate > 0.0 { params.max_turn_rate * dt } else { std::f32::consts::TAU };
+    let c = heading.dot(wanted).clamp(-1.0, 1.0);
+    let turned = if c.acos() <= max_angle {
+        wanted
+    } else {
+        // any side when the desired heading is straight behind
+        let mut side = wanted - heading * c;
+        if side.length() < 1.0e-6 {
+            side = Vec2 { x: heading.y, y: -heading.x };
+        }
+        heading * max_angle.cos() + side.normalize_or(Vec2::ZERO) * max_angle.sin()
+    };
+    let speed = desired.length().clamp(params.min_speed, params.min_speed.max(params.max_speed));
+    turned * speed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{FALLOFF_STEP, INTEGRATOR_SEMI_IMPLICIT_EULER};
+
+    fn boid(pos: [f32; 2], vel: [f32; 2]) -> Boid {
+        Boid { pos, vel }
+    }
+
+    fn assert_close(a: [f32; 2], b: [f32; 2]) {
+        assert!((a[0] - b[0]).abs() < 1.0e-6 && (a[1] - b[1]).abs() < 1.0e-6, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn a_lone_boid_flies_straight() {
+        let params = SimParams::default();
+        let mut boids = vec![boid([0.0, 0.0], [0.2, 0.1])];
+        for _ in 0..10 {
+            boids = step(&boids, &params);
+        }
+        assert_close(boids[0].vel, [0.2, 0.1]);
+        assert_close(boids[0].pos, [0.2 * 10.0 * params.delta_t, 0.1 * 10.0 * params.delta_t]);
+    }
+
+    #[test]
+    fn every_integrator_advances_as_expected() {
+        // with drag slowing the lone boid down, the integrators differ in which velocity moves it
+        let mut params = SimParams { drag: 1.0, ..SimParams::default() };
+        let dt = params.delta_t;
+        let slowed = |t: f32| 0.2 * (-params.drag * t).exp();
+        for (integrator, x) in [
+            (INTEGRATOR_EXPLICIT_EULER, 0.2 * dt),
+            (INTEGRATOR_SEMI_IMPLICIT_EULER, slowed(dt) * dt),
+            (INTEGRATOR_VELOCITY_VERLET, 0.2 * dt),
+            (INTEGRATOR_RK2, slowed(dt * 0.5) * dt),
+        ] {
+            params.integrator = integrator;
+            let next = step(&[boid([0.0, 0.0], [0.2, 0.0])], &params)[0];
+            assert_close(next.pos, [x, 0.0]);
+            assert_close(next.vel, [slowed(dt), 0.0]);
+        }
+    }
+
+    #[test]
+    fn boids_at_rest_stay_finite() {
+        // two of them on top of each other, so the distance between them is 0 too
+        let boids = [boid([0.0, 0.0], [0.0, 0.0]), boid([0.0, 0.0], [0.0, 0.0]), boid([0.5, 0.5], [0.0, 0.0])];
+        // without a minimum speed they stay put, with one they set off at it
+        for min_speed in [0.0, 0.05] {
+            for integrator in [INTEGRATOR_EXPLICIT_EULER, INTEGRATOR_SEMI_IMPLICIT_EULER, INTEGRATOR_VELOCITY_VERLET, INTEGRATOR_RK2] {
+                for next in step(&boids, &SimParams { integrator, min_speed, ..SimParams::default() }) {
+                    assert!(next.pos.iter().chain(&next.vel).all(|v| v.is_finite()), "{:?}", next);
+                    assert!((Vec2::new(next.vel).length() - min_speed).abs() < 1.0e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn turns_are_limited_only_when_asked() {
+        let params = SimParams::default();
+        let dt = params.delta_t;
+        let turned = |max_turn_rate| {
+            let vel = fly(0, Vec2 { x: 0.2, y: 0.0 }, Vec2 { x: 0.0, y: -100.0 }, dt, &SimParams { max_turn_rate, ..params });
+            vel.y.atan2(vel.x).abs()
+        };
+        assert!((turned(1.0) - dt).abs() < 1.0e-5);
+        // a max turn rate of 0 lets the boid head wherever the steering takes it
+        assert!((turned(0.0) - (3.0f32).atan2(0.2)).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn neighbors_beyond_perception_are_ignored() {
+        let params = SimParams::default();
+        let lone = step(&[boid([0.0, 0.0], [0.2, 0.0])], &params)[0];
+        let far = step(&[boid([0.0, 0.0], [0.2, 0.0]), boid([0.0, 0.15], [-0.2, 0.1])], &params)[0];
+        assert_eq!((far.pos, far.vel), (lone.pos, lone.vel));
+        // the same neighbor within perception turns it
+        let near = step(&[boid([0.0, 0.0], [0.2, 0.0]), boid([0.0, 0.05], [-0.2, 0.1])], &params)[0];
+        assert_ne!((near.pos, near.vel), (lone.pos, lone.vel));
+    }
+
+    #[test]
+    fn topological_neighbors_are_the_closest() {
+        let boids = [
+            boid([0.0, 0.0], [0.1, 0.0]),
+            boid([0.5, 0.0], [0.1, 0.0]),
+            boid([0.1, 0.0], [0.1, 0.0]),
+            boid([0.0, -0.3], [0.1, 0.0]),
+        ];
+        assert_eq!(nearest(&boids, 0, 2, -1.0), [2, 3]);
+        // a blind spot so wide only boids within 60 degrees of the heading are seen leaves out boid 3 at the side
+        assert_eq!(nearest(&boids, 0, 2, 0.5), [2, 1]);
+    }
+
+    #[test]
+    fn falloffs_fade_out_at_the_radius() {
+        for kind in [FALLOFF_STEP, FALLOFF_LINEAR, FALLOFF_SMOOTHSTEP, FALLOFF_INVERSE_SQUARE] {
+            assert_eq!(falloff(kind, 0.0, 0.1), 1.0);
+            assert_eq!(falloff(kind, 0.2, 0.1), 0.0);
+            assert_eq!(falloff(kind, 0.0, 0.0), 0.0);
+        }
+        assert_eq!(falloff(FALLOFF_STEP, 0.1, 0.1), 1.0);
+        assert_eq!(falloff(FALLOFF_LINEAR, 0.05, 0.1), 0.5);
+        assert_eq!(falloff(FALLOFF_SMOOTHSTEP, 0.05, 0.1), 0.5);
+        assert!(falloff(FALLOFF_INVERSE_SQUARE, 0.1, 0.1).abs() < 1.0e-6);
+    }
+}
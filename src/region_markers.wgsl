@@ -0,0 +1,38 @@
+// Circles showing the emitters and sinks, sinks dashed
+
+#import region_types.wgsl
+#import math.wgsl
+
+@group(0) @binding(0)
+var<storage, read> regions: RegionList;
+
+const CIRCLE_SEGMENTS : u32 = 48u;
+const MARKER_VERTICES : u32 = 2u * CIRCLE_SEGMENTS;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn region_vs_main(@builtin(vertex_index) index: u32, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let region = regions.regions[instance];
+    var segment = index / 2u + index % 2u;
+    var out: VertexOutput;
+    out.color = vec3<f32>(0.3, 0.9, 1.0); // emitters cyan
+    if (region.kind == REGION_SINK) {
+        out.color = vec3<f32>(1.0, 0.6, 0.2); // sinks orange
+        // every other segment collapses to a point
+        if ((index / 2u) % 2u == 1u) {
+            segment = index / 2u;
+        }
+    }
+    let angle = f32(segment) / f32(CIRCLE_SEGMENTS) * TAU;
+    out.clip_position = vec4<f32>(region.position.xy + vec2<f32>(cos(angle), sin(angle)) * region.radius, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn region_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,31 @@
+// Region and RegionList structs that match population.rs, read by population.wgsl and region_markers.wgsl
+
+struct Region {
+    position: vec3<f32>,
+    radius: f32,
+    kind: u32,
+    spawn: u32,
+    first: u32,
+    _pad: u32,
+}
+
+struct RegionList {
+    count: u32,
+    spawn_total: u32,
+    burst: u32,
+    seed: u32,
+    stride: u32,
+    capacity: u32,
+    num_vertices: u32,
+    resize_to: u32,
+    distribution: u32,
+    // pads the regions out to 48 bytes in, where population.rs writes them
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+    regions: array<Region, MAX_REGIONS>,
+}
+
+// must match population.rs
+const MAX_REGIONS : u32 = 32u;
+const REGION_SINK : u32 = 1u;
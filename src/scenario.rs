@@ -0,0 +1,66 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{arena::ArenaSettings, forces::ForceSource, leaders::LeaderSettings, models::BehaviorModel, params::SimParams, paths::WaypointPath, population::Region, sim::Simulation};
+
+// A saved setup of the simulation, stored as a RON file. Anything left out of a file keeps its default
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub params: SimParams,
+    pub forces: Vec<ForceSource>,
+    pub paths: Vec<WaypointPath>,
+    pub leaders: LeaderSettings,
+    // emitters and sinks
+    pub regions: Vec<Region>,
+    // the walls of the 2D world
+    pub arena: ArenaSettings,
+    pub model: BehaviorModel,
+    // WGSL source of a user extra_force, see snippet.rs
+    pub extra_force: Option<String>,
+}
+
+impl Scenario {
+    // the current setup of `sim`
+    pub fn from_sim(sim: &Simulation) -> Self {
+        Self {
+            params: sim.params,
+            forces: sim.forces.sources.clone(),
+            paths: sim.paths.paths.clone(),
+            leaders: sim.leaders.settings.clone(),
+            regions: sim.lifecycle.regions.clone(),
+            arena: sim.arena.settings.clone(),
+            model: sim.model,
+            extra_force: sim.extra_force.clone(),
+        }
+    }
+
+    // set `sim` up like this, keeping its boids
+    pub fn apply(self, sim: &mut Simulation) {
+        sim.params = SimParams { num_boids: sim.num_instances, ..self.params };
+        sim.forces.set_sources(self.forces);
+        sim.paths.set_paths(self.paths);
+        sim.leaders.set_settings(self.leaders);
+        sim.lifecycle.set_regions(self.regions);
+        sim.arena.set_settings(self.arena);
+        sim.model = self.model;
+        if self.extra_force != sim.extra_force && let Err(err) = sim.set_extra_force(self.extra_force) {
+            log::error!("Scenario's extra_force doesn't compile, keeping the current one:\n{}", err);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        fs::write(path, text)
+    }
+}
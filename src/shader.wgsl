@@ -32,15 +32,26 @@ fn boid_vs_main(
     @location(0) instance_pos: vec2<f32>,
     @location(1) instance_vel: vec2<f32>,
     @location(2) vertex_pos: vec2<f32>,
+    @location(5) flags: u32,
 ) -> VertexOutput {
     var out: VertexOutput;
-    let angle = -atan2(instance_vel.x, instance_vel.y);
+    // a boid at a standstill points up
+    var angle = 0.0;
+    if (length(instance_vel) > 0.0) {
+        angle = -atan2(instance_vel.x, instance_vel.y);
+    }
+    // leaders are drawn bigger, see leaders.wgsl for the flags
+    let leader = (flags & 1u) != 0u;
+    let vertex = vertex_pos * select(1.0, 1.6, leader);
     let pos = vec2<f32>(
-        vertex_pos.x * cos(angle) - vertex_pos.y * sin(angle),
-        vertex_pos.x * sin(angle) + vertex_pos.y * cos(angle)
+        vertex.x * cos(angle) - vertex.y * sin(angle),
+        vertex.x * sin(angle) + vertex.y * cos(angle)
     );
     out.clip_position = vec4<f32>(instance_pos + pos, 0.0, 1.0);
     out.color = vec3<f32>(0.9, 0.6, 0.6); // light pink
+    if (leader) {
+        out.color = vec3<f32>(1.0, 0.8, 0.2); // gold
+    }
     return out;
 }
 
@@ -0,0 +1,427 @@
+use std::borrow::Cow;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::{Path, PathBuf}, time::{Duration, Instant, SystemTime}};
+
+use wgpu::naga;
+
+// Every WGSL file of the crate, baked into the binary. A file can pull others in with `#import name.wgsl` lines,
+// so the structs matching the Rust side (the *_types.wgsl files) and the shared helpers are written down once
+macro_rules! baked {
+    ($($name:literal),* $(,)?) => {
+        [$(($name, include_str!($name))),*]
+    };
+}
+
+const FILES: [(&str, &str); 35] = baked![
+    "alive.wgsl",
+    "arena.wgsl",
+    "arena_lines.wgsl",
+    "arena_types.wgsl",
+    "boid2d_types.wgsl",
+    "boid3d.wgsl",
+    "boid3d_types.wgsl",
+    "camera_types.wgsl",
+    "compute.wgsl",
+    "compute3d.wgsl",
+    "debug.wgsl",
+    "debug_types.wgsl",
+    "flight.wgsl",
+    "flow.wgsl",
+    "flow_arrows.wgsl",
+    "force_markers.wgsl",
+    "force_types.wgsl",
+    "forces.wgsl",
+    "globe.wgsl",
+    "grid.wgsl",
+    "leader_types.wgsl",
+    "leaders.wgsl",
+    "math.wgsl",
+    "models.wgsl",
+    "params_types.wgsl",
+    "path_lines.wgsl",
+    "path_types.wgsl",
+    "paths.wgsl",
+    "population.wgsl",
+    "postprocess.wgsl",
+    "random.wgsl",
+    "region_markers.wgsl",
+    "region_types.wgsl",
+    "shader.wgsl",
+    "sphere.wgsl",
+];
+
+const IMPORT: &str = "#import ";
+
+// how often the files are checked for changes
+#[cfg(not(target_arch = "wasm32"))]
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Where the shader files come from: the copies baked in, or, while developing, the files on disk,
+// watched for changes so the pipelines using them can be rebuilt without a restart
+#[derive(Default)]
+pub struct ShaderSources {
+    #[cfg(not(target_arch = "wasm32"))]
+    watch: Option<Watch>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Watch {
+    dir: PathBuf,
+    // modification time of every file as of the last poll, None when it couldn't be read
+    stamps: Vec<Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl ShaderSources {
+    // read the files from `dir` from now on, every one of them counting as changed
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn watching(dir: &Path) -> Self {
+        Self {
+            watch: Some(Watch {
+                dir: dir.to_path_buf(),
+                stamps: vec![None; FILES.len()],
+                last_poll: Instant::now() - POLL_INTERVAL,
+            }),
+        }
+    }
+
+    // the text of the file `name`, as written
+    pub fn get(&self, name: &str) -> Result<Cow<'static, str>, String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(watch) = &self.watch {
+            let path = watch.dir.join(name);
+            return fs::read_to_string(&path).map(Cow::Owned).map_err(|err| format!("{}: {}", path.display(), err));
+        }
+        FILES.iter().find(|(file, _)| *file == name)
+            .map(|(_, source)| Cow::Borrowed(*source))
+            .ok_or_else(|| format!("no shader named {}", name))
+    }
+
+    // The shader `name` ready to compile: every file it imports, and everything those import, is put in
+    // front of it once, before the first file importing it. The import lines are left blank, so each file
+    // keeps its own line numbers
+    pub fn compose(&self, name: &str) -> Result<Composed, String> {
+        let name = known(name)?;
+        let mut composed = Composed { name, source: String::new(), parts: Vec::new() };
+        self.include(name, &mut composed, &mut Vec::new())?;
+        Ok(composed)
+    }
+
+    fn include(&self, name: &'static str, composed: &mut Composed, importing: &mut Vec<&'static str>) -> Result<(), String> {
+        if composed.parts.iter().any(|&(part, _)| part == name) {
+            return Ok(());
+        }
+        if importing.contains(&name) {
+            return Err(format!("import cycle: {} -> {}", importing.join(" -> "), name));
+        }
+        let text = self.get(name)?;
+        importing.push(name);
+        let mut body = String::with_capacity(text.len());
+        for line in text.lines() {
+            if let Some(import) = line.trim().strip_prefix(IMPORT) {
+                let import = known(import.trim()).map_err(|err| format!("{}: {}", name, err))?;
+                self.include(import, composed, importing)?;
+            } else {
+                body.push_str(line);
+            }
+            body.push('\n');
+        }
+        importing.pop();
+        composed.push(name, &body);
+        Ok(())
+    }
+
+    // the files modified since the last call, looked at no more often than every POLL_INTERVAL
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn changed(&mut self) -> Vec<&'static str> {
+        let Some(watch) = &mut self.watch else {
+            return Vec::new();
+        };
+        if watch.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        watch.last_poll = Instant::now();
+        FILES.iter().zip(&mut watch.stamps).filter_map(|(&(name, _), stamp)| {
+            let modified = fs::metadata(watch.dir.join(name)).and_then(|metadata| metadata.modified()).ok();
+            // a file mid-save may be missing for a moment, it counts as changed once it's back
+            if modified.is_none() || modified == *stamp {
+                return None;
+            }
+            *stamp = modified;
+            Some(name)
+        }).collect()
+    }
+}
+
+// `name` as it appears in FILES
+fn known(name: &str) -> Result<&'static str, String> {
+    FILES.iter().map(|&(file, _)| file).find(|&file| file == name).ok_or_else(|| format!("no shader named {}", name))
+}
+
+// the baked shader `name` with its imports, for the pipelines that are never reloaded
+pub fn builtin(name: &str) -> String {
+    ShaderSources::default().compose(name).expect("the baked shaders compose").source
+}
+
+// A shader put together from its imports, remembering which file every line came from
+pub struct Composed {
+    name: &'static str,
+    pub source: String,
+    // each file and the line of `source` it starts at, counting from 1
+    parts: Vec<(&'static str, usize)>,
+}
+
+impl Composed {
+    fn push(&mut self, name: &'static str, text: &str) {
+        self.parts.push((name, self.source.lines().count() + 1));
+        self.source.push_str(text);
+    }
+
+    // `text` put in front of everything else, called `name` in errors
+    pub fn prepend(self, name: &'static str, text: &str) -> Self {
+        let mut text = text.to_string();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        let shift = text.lines().count();
+        let mut parts = vec![(name, 1)];
+        parts.extend(self.parts.into_iter().map(|(part, start)| (part, start + shift)));
+        Self { name: self.name, source: text + &self.source, parts }
+    }
+
+    // the files this shader is made of, its own last
+    pub fn files(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.parts.iter().map(|&(part, _)| part)
+    }
+
+    // the file line `line` of the source came from, and the line in it
+    fn origin(&self, line: usize) -> Option<(&'static str, usize)> {
+        self.parts.iter().rev().find(|&&(_, start)| start <= line).map(|&(part, start)| (part, line - start + 1))
+    }
+
+    // where in which file an error at `location` is, followed by naga's report on the whole source
+    fn report(&self, location: Option<naga::SourceLocation>, emitted: String) -> String {
+        match location.and_then(|location| self.origin(location.line_number as usize)) {
+            Some((file, line)) => format!("{}:{}: in {}\n{}", file, line, self.name, emitted),
+            None => emitted,
+        }
+    }
+
+    // the parsed shader, for reflection
+    pub fn module(&self) -> Result<naga::Module, String> {
+        naga::front::wgsl::parse_str(&self.source)
+            .map_err(|err| self.report(err.location(&self.source), err.emit_to_string_with_path(&self.source, self.name)))
+    }
+
+    // Parse and validate the shader with naga, returning the error with the offending lines quoted
+    // instead of letting wgpu panic on it
+    pub fn validate(&self) -> Result<(), String> {
+        let module = self.module()?;
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+            .validate(&module)
+            .map_err(|err| self.report(err.location(&self.source), err.emit_to_string_with_path(&self.source, self.name)))?;
+        Ok(())
+    }
+}
+
+// offsets of the fields of a Rust struct, under the names the WGSL struct gives them, for `assert_layout`
+#[cfg(test)]
+macro_rules! offsets {
+    ($ty:ty { $($field:ident $(as $wgsl:ident)?),* $(,)? }) => {
+        &[$((offsets!(@name $field $($wgsl)?), std::mem::offset_of!($ty, $field))),*]
+    };
+    (@name $field:ident $wgsl:ident) => { stringify!($wgsl) };
+    (@name $field:ident) => { stringify!($field) };
+}
+#[cfg(test)]
+pub(crate) use offsets;
+
+// the members of struct `name` in the shader `shader`, with their offsets, and its size
+#[cfg(test)]
+pub(crate) fn wgsl_struct(shader: &str, name: &str) -> (Vec<(String, usize)>, usize) {
+    let module = ShaderSources::default().compose(shader).and_then(|composed| composed.module()).unwrap();
+    module.types.iter()
+        .find_map(|(_, ty)| match &ty.inner {
+            naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => Some((
+                members.iter().map(|member| (member.name.clone().unwrap_or_default(), member.offset as usize)).collect(),
+                *span as usize,
+            )),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("no struct {} in {}", name, shader))
+}
+
+// Checks that struct `name` of the shader `shader` starts with `fields` at the same offsets as in Rust,
+// and, given a `size`, that it has nothing else and is that big
+#[cfg(test)]
+pub(crate) fn assert_layout(shader: &str, name: &str, fields: &[(&str, usize)], size: Option<usize>) {
+    let (members, span) = wgsl_struct(shader, name);
+    let wgsl: Vec<(&str, usize)> = members.iter().map(|(member, offset)| (member.as_str(), *offset)).collect();
+    match size {
+        Some(size) => {
+            assert_eq!(wgsl, fields, "members of {} in {}", name, shader);
+            assert_eq!(span, size, "size of {} in {}", name, shader);
+        }
+        None => assert_eq!(&wgsl[..fields.len().min(wgsl.len())], fields, "members of {} in {}", name, shader),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{boid::{Boid, Boid3d}, debug::DEBUG_BUFFER_SIZE, flow::FlowParams, grid::GridParams, leaders::LeaderParams, params::SimParams, postprocess::PostUniforms, sim::{boid_vertex_buffers, Space, COMPUTE_STORAGE_BUFFERS}, snippet};
+
+    // the shaders pipelines are made from, everything else is only imported
+    const ENTRY_POINTS: [&str; 14] = [
+        "arena_lines.wgsl", "compute.wgsl", "compute3d.wgsl", "shader.wgsl", "boid3d.wgsl", "debug.wgsl", "flow_arrows.wgsl", "globe.wgsl",
+        "force_markers.wgsl", "grid.wgsl", "path_lines.wgsl", "population.wgsl", "region_markers.wgsl", "postprocess.wgsl",
+    ];
+
+    fn compose(name: &str) -> Composed {
+        let composed = ShaderSources::default().compose(name).unwrap();
+        // the kernels call extra_force, which sim.rs puts in front of them
+        match name {
+            "compute.wgsl" => composed.prepend("extra_force", &snippet::default_extra_force(Space::Plane)),
+            "compute3d.wgsl" => composed.prepend("extra_force", &snippet::default_extra_force(Space::Volume)),
+            _ => composed,
+        }
+    }
+
+    #[test]
+    fn every_shader_validates() {
+        for name in ENTRY_POINTS {
+            if let Err(err) = compose(name).validate() {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn every_file_is_used() {
+        for (file, _) in FILES {
+            assert!(ENTRY_POINTS.iter().any(|&name| compose(name).files().any(|part| part == file)), "{} isn't used", file);
+        }
+    }
+
+    #[test]
+    fn imports_are_included_once_keeping_their_lines() {
+        let composed = compose("compute.wgsl");
+        let files: Vec<_> = composed.files().collect();
+        for file in &files {
+            assert_eq!(files.iter().filter(|&other| other == file).count(), 1, "{} included twice", file);
+        }
+        // math.wgsl is imported by the kernel, flight.wgsl and leaders.wgsl, and goes in before the first of them
+        let position = |name| files.iter().position(|&file| file == name).unwrap();
+        assert!(position("math.wgsl") < position("flight.wgsl"));
+        assert_eq!(composed.source.matches("struct Boid {").count(), 1);
+        let line = composed.source.lines().position(|line| line.starts_with("struct SimParams")).unwrap() + 1;
+        let expected = ShaderSources::default().get("params_types.wgsl").unwrap().lines().position(|line| line.starts_with("struct SimParams")).unwrap() + 1;
+        assert_eq!(composed.origin(line), Some(("params_types.wgsl", expected)));
+    }
+
+    #[test]
+    fn errors_point_into_the_file() {
+        let composed = compose("compute.wgsl").prepend("extra_force", "fn broken( {\n");
+        let err = composed.validate().unwrap_err();
+        assert!(err.starts_with("extra_force:1:"), "{}", err);
+    }
+
+    #[test]
+    fn kernels_fit_the_requested_storage_buffers() {
+        for name in ["compute.wgsl", "compute3d.wgsl"] {
+            let module = compose(name).module().unwrap();
+            let storage = module.global_variables.iter()
+                .filter(|(_, var)| matches!(var.space, naga::AddressSpace::Storage { .. }))
+                .count();
+            assert!(storage as u32 <= COMPUTE_STORAGE_BUFFERS, "{} binds {} storage buffers", name, storage);
+        }
+    }
+
+    #[test]
+    fn boids_match() {
+        let size = std::mem::size_of::<Boid>();
+        assert_layout("boid2d_types.wgsl", "Boid", offsets!(Boid { pos, vel }), Some(size));
+        let size = std::mem::size_of::<Boid3d>();
+        assert_layout("boid3d_types.wgsl", "Boid", offsets!(Boid3d { pos, vel }), Some(size));
+    }
+
+    #[test]
+    fn sim_params_match() {
+        let fields = offsets!(SimParams {
+            alignment_distance, separation_distance, cohesion_weight, alignment_weight, separation_weight, max_speed,
+            delta_t, num_boids, fov, blind_spot, neighbor_mode, k_neighbors, cohesion_distance, separation_falloff,
+            alignment_falloff, cohesion_falloff, wander, wander_rate, seed, step, max_force, max_turn_rate, min_speed,
+            drag, integrator, heading_noise, cucker_smale_strength, cucker_smale_beta, morse_attraction,
+            morse_attraction_range, morse_repulsion, morse_repulsion_range, propulsion, friction,
+        });
+        assert_layout("params_types.wgsl", "SimParams", fields, Some(std::mem::size_of::<SimParams>()));
+    }
+
+    #[test]
+    fn uniforms_match() {
+        let fields = offsets!(LeaderParams { mode, influence, steering, flocking, goal, speed, time });
+        assert_layout("leader_types.wgsl", "LeaderParams", fields, Some(std::mem::size_of::<LeaderParams>()));
+        let fields = offsets!(FlowParams { kind, strength, time, noise_scale, wind, time_scale, grid_size });
+        assert_layout("flow.wgsl", "FlowParams", fields, Some(std::mem::size_of::<FlowParams>()));
+        let fields = offsets!(GridParams { dims, stride, _pad0, num_cells, _pad });
+        assert_layout("grid.wgsl", "GridParams", fields, Some(std::mem::size_of::<GridParams>()));
+        let fields = offsets!(PostUniforms { strength, threshold, radius, _pad, texel, direction });
+        assert_layout("postprocess.wgsl", "PostUniforms", fields, Some(std::mem::size_of::<PostUniforms>()));
+    }
+
+    #[test]
+    fn debug_info_fits_the_buffer() {
+        let (_, size) = wgsl_struct("debug_types.wgsl", "DebugInfo");
+        assert_eq!(size, DEBUG_BUFFER_SIZE as usize);
+    }
+
+    // naga's type of a vertex input as a vertex format
+    fn vertex_format(inner: &naga::TypeInner) -> Option<wgpu::VertexFormat> {
+        use naga::{ScalarKind, TypeInner, VectorSize};
+        use wgpu::VertexFormat;
+        match *inner {
+            TypeInner::Scalar(scalar) if scalar.width == 4 => match scalar.kind {
+                ScalarKind::Float => Some(VertexFormat::Float32),
+                ScalarKind::Uint => Some(VertexFormat::Uint32),
+                ScalarKind::Sint => Some(VertexFormat::Sint32),
+                _ => None,
+            },
+            TypeInner::Vector { size, scalar } if scalar.width == 4 && scalar.kind == ScalarKind::Float => Some(match size {
+                VectorSize::Bi => VertexFormat::Float32x2,
+                VectorSize::Tri => VertexFormat::Float32x3,
+                VectorSize::Quad => VertexFormat::Float32x4,
+            }),
+            _ => None,
+        }
+    }
+
+    // every location the vertex entry point reads comes from one of the buffers, in the same format
+    fn assert_vertex_inputs(shader: &str, entry_point: &str, space: Space) {
+        let module = compose(shader).module().unwrap();
+        let entry = module.entry_points.iter().find(|entry| entry.name == entry_point).unwrap();
+        let mut inputs: Vec<(u32, Option<wgpu::VertexFormat>)> = entry.function.arguments.iter()
+            .filter_map(|argument| match argument.binding {
+                Some(naga::Binding::Location { location, .. }) => Some((location, vertex_format(&module.types[argument.ty].inner))),
+                _ => None,
+            })
+            .collect();
+        inputs.sort_by_key(|&(location, _)| location);
+        let mut attributes: Vec<(u32, Option<wgpu::VertexFormat>)> = boid_vertex_buffers(space).iter()
+            .flat_map(|buffer| buffer.attributes.iter().map(|attribute| (attribute.shader_location, Some(attribute.format))))
+            .collect();
+        attributes.sort_by_key(|&(location, _)| location);
+        assert_eq!(inputs, attributes, "vertex inputs of {}", entry_point);
+    }
+
+    #[test]
+    fn vertex_inputs_match() {
+        assert_vertex_inputs("shader.wgsl", "boid_vs_main", Space::Plane);
+        assert_vertex_inputs("boid3d.wgsl", "boid3d_vs_main", Space::Volume);
+        // the instance attributes read the boid fields
+        let offsets = |buffer: &wgpu::VertexBufferLayout| buffer.attributes.iter().map(|attribute| attribute.offset as usize).collect::<Vec<_>>();
+        assert_eq!(offsets(&Boid::desc()), [std::mem::offset_of!(Boid, pos), std::mem::offset_of!(Boid, vel)]);
+        assert_eq!(Boid::desc().array_stride as usize, std::mem::size_of::<Boid>());
+        assert_eq!(offsets(&Boid3d::desc()), [std::mem::offset_of!(Boid3d, pos), std::mem::offset_of!(Boid3d, vel)]);
+        assert_eq!(Boid3d::desc().array_stride as usize, std::mem::size_of::<Boid3d>());
+    }
+}
@@ -0,0 +1,1030 @@
+use std::borrow::Cow;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use log::debug;
+use wgpu::{util::DeviceExt, PipelineLayoutDescriptor};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::capture::{timestamped_filename, write_png};
+use crate::{arena::Arena, boid::{cone_vertices, generate_boids, generate_boids_3d, generate_boids_sphere, mesh_buffer_layout, triangle_buffer_layout, Boid, Boid3d, TRIANGLE_VERTICES}, camera::{CameraUniform, OrbitCamera}, capture::OffscreenTarget, debug::{DebugOverlay, DEBUG_BUFFER_SIZE}, flow::FlowField, forces::Forces, globe::Globe, grid::NeighborGrid, leaders::Leaders, models::{BehaviorModel, ModelKernels}, params::{SimParams, NEIGHBORS_TOPOLOGICAL}, paths::Paths, population::Population, postprocess::{PostConfig, PostProcessor, DEPTH_FORMAT, HDR_FORMAT}, shaders::{self, Composed, ShaderSources}, snippet};
+
+// number of boids at startup and after a reset
+pub const POPULATION : u32 = 50000;
+// the instance buffers have room for at least this many boids so more can be spawned at runtime
+pub const MAX_POPULATION : u32 = 100000;
+// storage buffers bound by the compute kernels, more than the default 8. shaders.rs checks it against the kernels
+pub const COMPUTE_STORAGE_BUFFERS : u32 = 11;
+// format of offscreen targets when there is no surface to match
+pub const HEADLESS_FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// Where the boids live, chosen when the simulation is created
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Space {
+    // the [-1, 1] square filling the window
+    #[default]
+    Plane,
+    // the [-1, 1] cube, seen through a perspective camera
+    Volume,
+    // the surface of the unit sphere, seen as a globe through the same camera
+    Sphere,
+}
+
+impl Space {
+    // bytes per boid in the instance buffers
+    pub fn boid_size(self) -> u64 {
+        match self {
+            Space::Plane => std::mem::size_of::<Boid>() as u64,
+            Space::Volume | Space::Sphere => std::mem::size_of::<Boid3d>() as u64,
+        }
+    }
+
+    // whether the boids are vec3s seen through the orbit camera
+    pub fn is_3d(self) -> bool {
+        self != Space::Plane
+    }
+
+    // the compute shader, which imports the rest of the kernel
+    fn compute_shader(self) -> &'static str {
+        match self {
+            Space::Plane => "compute.wgsl",
+            Space::Volume | Space::Sphere => "compute3d.wgsl",
+        }
+    }
+
+    fn render_shader(self) -> &'static str {
+        match self {
+            Space::Plane => "shader.wgsl",
+            Space::Volume | Space::Sphere => "boid3d.wgsl",
+        }
+    }
+
+    // `count` new boids, ready to be written to an instance buffer
+    fn generate(self, count: u32) -> Vec<u8> {
+        match self {
+            Space::Plane => bytemuck::cast_slice(&generate_boids(count)).to_vec(),
+            Space::Volume => bytemuck::cast_slice(&generate_boids_3d(count)).to_vec(),
+            Space::Sphere => bytemuck::cast_slice(&generate_boids_sphere(count)).to_vec(),
+        }
+    }
+}
+
+// The boids on the GPU and everything needed to step and draw them.
+// It doesn't know about windows: frames are drawn into whatever texture view it's given,
+// which is the window surface for the interactive app and an offscreen texture otherwise.
+pub struct Simulation {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub space: Space,
+    // color format of every target the scene is drawn into
+    pub format: wgpu::TextureFormat,
+    pub render_pipeline: wgpu::RenderPipeline,
+    // a compute pipeline per behavior model, `model` is the one that runs
+    pub kernels: ModelKernels,
+    pub model: BehaviorModel,
+    // the user's WGSL defining extra_force, built into the kernels (see snippet.rs)
+    pub extra_force: Option<String>,
+    // why the last extra_force snippet was turned down
+    pub snippet_error: Option<String>,
+    // why the last shader reload was turned down
+    pub shader_error: Option<String>,
+    // where the shader files are read from when building pipelines
+    pub shaders: ShaderSources,
+    pub num_vertices: u32,
+    // slots in use in the instance buffers, alive or dead, as of the last readback from the GPU
+    pub num_instances: u32,
+    pub max_instances: u32,
+    // number of boids generated at startup and by `reset`
+    pub population: u32,
+    pub vertex_buffer: wgpu::Buffer,
+    pub instance_buffers: Vec<wgpu::Buffer>,
+    compute_bind_group_layout: wgpu::BindGroupLayout,
+    pub compute_bind_groups: Vec<wgpu::BindGroup>,
+    pub params: SimParams,
+    pub params_buffer: wgpu::Buffer,
+    // number of simulation steps taken, its parity tells which instance buffer holds the latest boids
+    pub step: usize,
+    pub paused: bool,
+    pub debug: DebugOverlay,
+    // spatial grid for the topological neighbor mode
+    pub grid: NeighborGrid,
+    // wind and other external flow the boids drift in
+    pub flow: FlowField,
+    // attractors, repellers and vortices
+    pub forces: Forces,
+    // waypoint paths some of the boids steer along
+    pub paths: Paths,
+    // the walls of the 2D world
+    pub arena: Arena,
+    // boids the others follow more than their peers
+    pub leaders: Leaders,
+    // which slots hold live boids, with the emitters and sinks that change them
+    pub lifecycle: Population,
+    // effects applied between drawing the boids and the final target
+    pub post_config: PostConfig,
+    pub post: PostProcessor,
+    // only used in the 3D modes
+    pub camera: OrbitCamera,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    camera_bind_group: wgpu::BindGroup,
+    // the planet under the boids of the sphere mode
+    globe: Option<Globe>,
+}
+
+// handle for our graphics card, plus the device and queue we create on it
+pub async fn request_device(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+    force_fallback_adapter: bool,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), Box<dyn std::error::Error>> {
+    let adapter = instance.request_adapter(
+        &wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface,
+            force_fallback_adapter,
+        },
+    ).await?;
+    debug!("Using adapter {:?}", adapter.get_info());
+    let storage_buffers = adapter.limits().max_storage_buffers_per_shader_stage;
+    if !cfg!(target_arch = "wasm32") && storage_buffers < COMPUTE_STORAGE_BUFFERS {
+        return Err(format!(
+            "{} supports {} storage buffers per shader stage, the compute kernels need {}",
+            adapter.get_info().name, storage_buffers, COMPUTE_STORAGE_BUFFERS,
+        ).into());
+    }
+
+    // create device and queue
+    let (device, queue) = adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::empty(),
+            // WebGL doesn't support all of wgpu's features, so if
+            // we're building for the web, we'll have to disable some.
+            required_limits: if cfg!(target_arch = "wasm32") {
+                wgpu::Limits::downlevel_webgl2_defaults()
+            } else {
+                // the compute kernel binds more storage buffers than the default 8, the adapter has room for them as checked above
+                wgpu::Limits {
+                    max_storage_buffers_per_shader_stage: COMPUTE_STORAGE_BUFFERS.max(wgpu::Limits::default().max_storage_buffers_per_shader_stage),
+                    ..wgpu::Limits::default()
+                }
+            },
+            label: None,
+            memory_hints: Default::default(),
+            trace: wgpu::Trace::Off,
+        },
+    ).await?;
+    Ok((adapter, device, queue))
+}
+
+impl Simulation {
+    pub fn new(device: wgpu::Device, queue: wgpu::Queue, format: wgpu::TextureFormat, population: u32, space: Space) -> Self {
+        let population = population.min(MAX_POPULATION);
+        let boids = space.generate(population);
+        debug!("Generated {} {:?} boids", population, space);
+        // This is the boid instance buffer, which contains the information of the boids (position & velocity)
+        let num_instances = population;
+        let max_instances = MAX_POPULATION;
+        let boid_size = space.boid_size();
+
+        let camera = OrbitCamera::default();
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Camera Buffer"),
+            size: std::mem::size_of::<CameraUniform>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("camera bind group layout"),
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("camera bind group"),
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shaders = ShaderSources::default();
+        let render_pipeline = boid_render_pipeline(&device, space, &camera_bind_group_layout, &shaders::builtin(space.render_shader()));
+
+        // load compute shader
+        let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(compute_source(&shaders, space, None).expect("the baked shaders compose").source.into()),
+        });
+
+        let [flow_grid_entry, flow_params_entry] = FlowField::layout_entries(wgpu::ShaderStages::COMPUTE);
+        let [leader_flags_entry, leader_params_entry] = Leaders::layout_entries(wgpu::ShaderStages::COMPUTE);
+        let bind_group_layout  =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        // at least one boid, 16 bytes in 2D and 32 in 3D and on the sphere. The buffers are replaced when the population is resized
+                        min_binding_size: wgpu::BufferSize::new(boid_size),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(boid_size),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(DEBUG_BUFFER_SIZE),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<SimParams>() as _),
+                    },
+                    count: None,
+                },
+                // grid cell starts and the boid indices sorted by cell
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // flow field grid and parameters
+                flow_grid_entry,
+                flow_params_entry,
+                Forces::layout_entry(wgpu::ShaderStages::COMPUTE),
+                Paths::layout_entry(wgpu::ShaderStages::COMPUTE),
+                // which boids lead and where they go
+                leader_flags_entry,
+                leader_params_entry,
+                Population::layout_entry(wgpu::ShaderStages::COMPUTE),
+                Arena::layout_entry(wgpu::ShaderStages::COMPUTE),
+            ],
+            label: None,
+        });
+
+        let compute_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("compute"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let kernels = ModelKernels::new(&device, &compute_shader, compute_pipeline_layout, space, false);
+        // shared vertex buffer across all boids.
+        // Since each boid is essentially a triangle, we will redraw this one triangle instance N times,
+        // each with different parameters from the boids array. In 3D the triangle becomes a cone
+        let mesh = match space {
+            Space::Plane => bytemuck::cast_slice(&TRIANGLE_VERTICES).to_vec(),
+            Space::Volume | Space::Sphere => bytemuck::cast_slice(&cone_vertices()).to_vec(),
+        };
+        let num_vertices = match space {
+            Space::Plane => TRIANGLE_VERTICES.len() as u32,
+            Space::Volume | Space::Sphere => cone_vertices().len() as u32,
+        };
+        let vertex_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor{
+                label: Some("vertex buffer"),
+                contents: &mesh,
+                usage: wgpu::BufferUsages::VERTEX,
+            }
+        );
+
+        let instance_buffers = create_instance_buffers(&device, max_instances, boid_size);
+        for buffer in &instance_buffers {
+            queue.write_buffer(buffer, 0, &boids);
+        }
+        let params = SimParams {
+            num_boids: num_instances,
+            ..Default::default()
+        };
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Params Buffer"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // overlay for inspecting a single boid, the compute kernel writes into its buffer
+        let debug = DebugOverlay::new(&device, format, max_instances);
+        debug.init(&queue);
+        let post = PostProcessor::new(&device, format);
+
+        let mut lifecycle = Population::new(&device, format, space, &instance_buffers, max_instances, num_vertices);
+        lifecycle.reset(&queue, num_instances);
+        let grid = NeighborGrid::new(&device, space, &instance_buffers, &lifecycle, max_instances);
+        let flow = FlowField::new(&device, format);
+        let forces = Forces::new(&device, format);
+        let paths = Paths::new(&device, format);
+        let arena = Arena::new(&device, format);
+        let leaders = Leaders::new(&device, max_instances);
+        let globe = (space == Space::Sphere).then(|| Globe::new(&device, &camera_bind_group_layout));
+
+        let mut sim = Self {
+            device,
+            queue,
+            space,
+            format,
+            render_pipeline,
+            kernels,
+            model: BehaviorModel::default(),
+            extra_force: None,
+            snippet_error: None,
+            shader_error: None,
+            shaders,
+            num_vertices,
+            num_instances,
+            max_instances,
+            population,
+            vertex_buffer,
+            instance_buffers,
+            compute_bind_group_layout: bind_group_layout,
+            compute_bind_groups: Vec::new(),
+            params,
+            params_buffer,
+            step: 0,
+            paused: false,
+            debug,
+            grid,
+            flow,
+            forces,
+            paths,
+            arena,
+            leaders,
+            lifecycle,
+            post_config: PostConfig::default(),
+            post,
+            camera,
+            camera_buffer,
+            camera_bind_group_layout,
+            camera_bind_group,
+            globe,
+        };
+        sim.create_compute_bind_groups();
+        sim
+    }
+
+    // one compute bind group per instance buffer, reading it and writing the other
+    fn create_compute_bind_groups(&mut self) {
+        self.compute_bind_groups = (0..2).map(|i| {
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("compute bind group {}", i)),
+                layout: &self.compute_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.instance_buffers[i].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: self.instance_buffers[(i + 1) % 2].as_entire_binding(), // bind to opposite buffer
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.debug.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: self.params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: self.grid.cell_start.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: self.grid.sorted.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: self.flow.grid_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: self.flow.params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 8,
+                        resource: self.forces.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 9,
+                        resource: self.paths.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 10,
+                        resource: self.leaders.flag_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 11,
+                        resource: self.leaders.params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 12,
+                        resource: self.lifecycle.state_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 13,
+                        resource: self.arena.buffer.as_entire_binding(),
+                    },
+                ]
+            })
+        }).collect();
+    }
+
+    // Simulation without a window, drawing into offscreen textures only.
+    // `software` asks for a CPU adapter (e.g. lavapipe or llvmpipe), so no GPU or display server is needed.
+    pub async fn new_headless(software: bool, population: u32, space: Space) -> Result<Self, Box<dyn std::error::Error>> {
+        // every backend is allowed, software rasterizers are often only reachable through GL
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let (_, device, queue) = request_device(&instance, None, software).await?;
+        Ok(Self::new(device, queue, HEADLESS_FORMAT, population, space))
+    }
+
+    pub fn update(&mut self) {
+        // let pending buffer mappings complete without waiting on the GPU
+        let _ = self.device.poll(wgpu::PollType::Poll);
+        self.lifecycle.update();
+        self.num_instances = self.lifecycle.slots;
+        self.debug.update(&self.queue, self.num_instances);
+    }
+
+    // replace every boid with a freshly generated starting population
+    pub fn reset(&mut self) {
+        let boids = self.space.generate(self.population);
+        for buffer in &self.instance_buffers {
+            self.queue.write_buffer(buffer, 0, &boids);
+        }
+        self.lifecycle.reset(&self.queue, self.population);
+        self.num_instances = self.population;
+        self.debug.selected = None;
+        self.debug.init(&self.queue);
+    }
+
+    // Change the number of boids without a restart. The instance buffers are replaced with ones that have room
+    // for `count` boids and as many again to spawn, the boids in use are copied over and any that are missing are
+    // spawned from the lifecycle's distribution, all on the GPU so no frame waits for it.
+    // `count` is also the population `reset` goes back to from now on
+    pub fn set_population(&mut self, count: u32) {
+        let boid_size = self.space.boid_size();
+        let limit = self.max_population();
+        let count = count.clamp(1, limit);
+        let max_instances = count.saturating_mul(2).max(MAX_POPULATION).min(limit);
+        let instance_buffers = create_instance_buffers(&self.device, max_instances, boid_size);
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Resize Encoder"),
+        });
+        // the next step reads the boids the last one wrote
+        let output = self.step % 2;
+        let copied = self.max_instances.min(max_instances) as u64 * boid_size;
+        encoder.copy_buffer_to_buffer(&self.instance_buffers[output], 0, &instance_buffers[output], 0, copied);
+        self.lifecycle.resize(&self.device, &self.queue, &mut encoder, &instance_buffers, output, max_instances, count);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        self.instance_buffers = instance_buffers;
+        self.max_instances = max_instances;
+        self.population = count;
+        self.num_instances = self.num_instances.min(count);
+        self.grid.resize(&self.device, &self.instance_buffers, &self.lifecycle, max_instances);
+        self.leaders.resize(&self.device, max_instances);
+        self.debug.resize(&self.device, max_instances);
+        if self.debug.selected.is_some_and(|index| index >= count) {
+            self.debug.selected = None;
+            self.debug.init(&self.queue);
+        }
+        self.create_compute_bind_groups();
+        debug!("Resized to {} boids, room for {}", count, max_instances);
+    }
+
+    // the most boids the instance buffers can hold: they have to fit in one storage buffer binding,
+    // and a dispatch over all of them in workgroups of 64 has to fit in one dimension
+    pub fn max_population(&self) -> u32 {
+        let limits = self.device.limits();
+        let fit = (limits.max_storage_buffer_binding_size as u64 / self.space.boid_size()) as u32;
+        fit.min(limits.max_compute_workgroups_per_dimension.saturating_mul(64))
+    }
+
+    // add up to `count` boids on the next step, into free slots or after the ones in use, as many as there's room for
+    pub fn spawn(&mut self, count: u32) {
+        self.lifecycle.request_burst(count);
+    }
+
+    // advance the boids by one step, skipped while paused so the same boids are drawn again
+    pub fn encode_step(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.params.num_boids = self.num_instances;
+        self.params.step = self.step as u32;
+        self.queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&self.params));
+        self.paths.write(&self.queue);
+        self.arena.write(&self.queue);
+        if self.paused {
+            self.flow.write(&self.queue);
+            self.forces.write(&self.queue);
+            self.leaders.write(&self.queue);
+            return;
+        }
+        self.flow.advance(self.params.delta_t);
+        self.flow.write(&self.queue);
+        self.forces.advance(self.params.delta_t);
+        self.forces.write(&self.queue);
+        self.leaders.advance(self.params.delta_t);
+        self.leaders.write(&self.queue);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Compute Pass"), 
+                timestamp_writes: None 
+            });
+            if self.params.neighbor_mode == NEIGHBORS_TOPOLOGICAL {
+                self.grid.encode_build(&self.queue, &mut compute_pass, self.step % 2, &self.lifecycle);
+            }
+            compute_pass.set_pipeline(self.kernels.pipeline(self.model));
+            compute_pass.set_bind_group(0,&self.compute_bind_groups[self.step % 2], &[]);
+            self.lifecycle.dispatch(&mut compute_pass);
+        }
+        self.step += 1;
+        // births and deaths go into the boids just written, and set the counts for the next step
+        self.lifecycle.encode_update(&self.queue, encoder, self.step % 2, self.params.delta_t);
+    }
+
+    // draw the boids, run the post chain, and draw the debug overlay into `view` of the given size
+    pub fn draw_scene(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, width: u32, height: u32) {
+        let (scene_view, depth_view) = self.post.scene_views(&self.device, width, height);
+        if self.space.is_3d() {
+            let uniform = self.camera.uniform(width as f32 / height as f32);
+            self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&uniform));
+        }
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment { 
+                    view: &scene_view, 
+                    resolve_target: None, 
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color { 
+                            r: 0.3, 
+                            g: 0.0, 
+                            b: 0.075, 
+                            a: 1.0 
+                        }),
+                        store: wgpu::StoreOp::Store,
+
+                    },
+                })],
+                // only the 3D pipelines have a depth test
+                depth_stencil_attachment: self.space.is_3d().then_some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set:None,
+                timestamp_writes: None,
+            });
+        
+            if self.space.is_3d() {
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            }
+            if let Some(globe) = &self.globe {
+                globe.draw(&mut render_pass);
+            }
+            render_pass.set_pipeline(&self.render_pipeline);
+        
+            render_pass.set_vertex_buffer(0, self.instance_buffers[self.step % 2].slice(..));        // N boids
+            render_pass.set_vertex_buffer(1, self.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(2, self.leaders.flag_buffer.slice(..));
+
+            // 3 vertices (a cone in 3D), one instance per slot in use
+            self.lifecycle.draw_boids(&mut render_pass);
+        }
+
+        self.post.run(&self.device, &self.queue, encoder, &self.post_config, view, width, height, self.step);
+        // the overlays are drawn in 2D world coordinates, the volume and sphere show none of them
+        if self.space != Space::Plane {
+            return;
+        }
+
+        // the arena's walls, flow arrows, force sources, paths, emitters and sinks and the perception overlay for the selected boid, drawn on top of the flock and left out of the effects
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.arena.draw(&mut render_pass);
+        self.flow.draw(&mut render_pass);
+        self.forces.draw(&mut render_pass);
+        self.paths.draw(&mut render_pass);
+        self.lifecycle.draw(&mut render_pass);
+        self.debug.draw(&mut render_pass);
+    }
+
+    // step the simulation and draw the result into `view` of the given size.
+    // `after_submit` must be called once the encoder has been submitted.
+    pub fn encode_frame(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, width: u32, height: u32) {
+        self.encode_step(encoder);
+        self.draw_scene(encoder, view, width, height);
+        if self.space == Space::Plane {
+            self.debug.encode_pick(encoder, &self.instance_buffers[self.step % 2], self.num_instances);
+        }
+        self.lifecycle.encode_readback(encoder);
+    }
+
+    pub fn after_submit(&mut self) {
+        self.debug.map_pick();
+        self.lifecycle.map_readback();
+    }
+
+    // draw the current boids, without stepping, into a new offscreen texture of any size
+    pub fn render_offscreen(&mut self, width: u32, height: u32) -> std::io::Result<OffscreenTarget> {
+        let target = OffscreenTarget::new(&self.device, width, height, self.format)?;
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Offscreen Encoder"),
+        });
+        self.draw_scene(&mut encoder, &target.view, width, height);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        Ok(target)
+    }
+
+    // Rebuild the compute shader with `snippet` as its extra_force, or without one. A snippet that doesn't
+    // compile leaves the running kernels alone and comes back as the compiler's message
+    pub fn set_extra_force(&mut self, snippet: Option<String>) -> Result<(), String> {
+        match self.build_kernels(snippet.as_deref()) {
+            Ok(kernels) => {
+                self.kernels = kernels;
+                self.extra_force = snippet;
+                self.snippet_error = None;
+                Ok(())
+            }
+            Err(err) => {
+                self.snippet_error = Some(err.clone());
+                Err(err)
+            }
+        }
+    }
+
+    fn build_kernels(&self, snippet: Option<&str>) -> Result<ModelKernels, String> {
+        let composed = compute_source(&self.shaders, self.space, snippet)?;
+        composed.validate()?;
+        // naga accepting it doesn't mean the backend does
+        #[cfg(not(target_arch = "wasm32"))]
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(composed.source.into()),
+        });
+        // every model's pipeline, so switching models later can't run into an error outside the scope
+        let kernels = self.kernels.rebuilt(&self.device, &module, snippet.is_some());
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(err.to_string());
+        }
+        Ok(kernels)
+    }
+
+    // Read the shader files from `dir` from now on and rebuild the pipelines whenever they change
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn watch_shaders(&mut self, dir: &std::path::Path) {
+        self.shaders = ShaderSources::watching(dir);
+        self.reload_shaders();
+    }
+
+    // Rebuild the pipelines using any shader file changed on disk. One that doesn't compile is logged
+    // and the previous pipeline keeps running; the buffers, and so the boids, are left as they are.
+    // After a failed reload both pipelines are rebuilt on the next change, so the error only goes
+    // away once every shader compiles again
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload_shaders(&mut self) {
+        let changed = self.shaders.changed();
+        if changed.is_empty() {
+            return;
+        }
+        let retry = self.shader_error.is_some();
+        let mut errors = Vec::new();
+        if retry || self.uses_any(self.space.compute_shader(), &changed) {
+            match self.build_kernels(self.extra_force.as_deref()) {
+                Ok(kernels) => {
+                    self.kernels = kernels;
+                    log::info!("Reloaded the compute shader");
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        if retry || self.uses_any(self.space.render_shader(), &changed) {
+            match self.build_render_pipeline() {
+                Ok(pipeline) => {
+                    self.render_pipeline = pipeline;
+                    log::info!("Reloaded {}", self.space.render_shader());
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        for err in &errors {
+            log::error!("Shader reload failed, keeping the previous pipeline:\n{}", err);
+        }
+        self.shader_error = (!errors.is_empty()).then(|| errors.join("\n"));
+    }
+
+    // whether shader `name` is made of any of the `changed` files, or doesn't compose anymore
+    #[cfg(not(target_arch = "wasm32"))]
+    fn uses_any(&self, name: &str, changed: &[&str]) -> bool {
+        self.shaders.compose(name).map_or(true, |composed| composed.files().any(|file| changed.contains(&file)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn build_render_pipeline(&self) -> Result<wgpu::RenderPipeline, String> {
+        let composed = self.shaders.compose(self.space.render_shader())?;
+        composed.validate()?;
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = boid_render_pipeline(&self.device, self.space, &self.camera_bind_group_layout, &composed.source);
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(err.to_string());
+        }
+        Ok(pipeline)
+    }
+
+    // the boids in use of the 2D mode as the last step left them, waiting for the GPU
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_boids(&self) -> std::io::Result<Vec<Boid>> {
+        if self.space != Space::Plane {
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "only 2D boids can be read back"));
+        }
+        // an empty buffer can't be mapped
+        if self.num_instances == 0 {
+            return Ok(Vec::new());
+        }
+        let size = self.num_instances as u64 * self.space.boid_size();
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Boid Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Boid Readback Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&self.instance_buffers[self.step % 2], 0, &staging_buffer, 0, size);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        staging_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::PollType::Wait).map_err(std::io::Error::other)?;
+        receiver.recv()
+            .map_err(std::io::Error::other)?
+            .map_err(std::io::Error::other)?;
+        let boids = bytemuck::pod_collect_to_vec(&staging_buffer.slice(..).get_mapped_range());
+        staging_buffer.unmap();
+        Ok(boids)
+    }
+
+    // render the current boids at any resolution and save them as a PNG named after the current time.
+    // Returns the path that was written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn screenshot(&mut self, width: u32, height: u32) -> std::io::Result<PathBuf> {
+        let target = self.render_offscreen(width, height)?;
+        let pixels = target.read_rgba(&self.device, &self.queue)?;
+        let path = PathBuf::from(timestamped_filename("screenshot", "png"));
+        write_png(&path, width, height, &pixels)?;
+        Ok(path)
+    }
+}
+
+// the whole compute shader of `space`, with the user's extra_force in front
+fn compute_source(shaders: &ShaderSources, space: Space, extra_force: Option<&str>) -> Result<Composed, String> {
+    let snippet = extra_force.map_or_else(|| Cow::Owned(snippet::default_extra_force(space)), Cow::Borrowed);
+    Ok(shaders.compose(space.compute_shader())?.prepend("extra_force", &snippet))
+}
+
+// the ping-pong pair of instance buffers, with room for `max_instances` boids each
+fn create_instance_buffers(device: &wgpu::Device, max_instances: u32, boid_size: u64) -> Vec<wgpu::Buffer> {
+    (0..2).map(|_| device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Instance Buffer"),
+        size: max_instances as u64 * boid_size,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    })).collect()
+}
+
+// What the boid render shader of `space` reads: the boid instances, the shared triangle or cone,
+// and the leader flags to color the leaders
+pub fn boid_vertex_buffers(space: Space) -> [wgpu::VertexBufferLayout<'static>; 3] {
+    match space {
+        Space::Plane => [Boid::desc(), triangle_buffer_layout(), Leaders::flag_buffer_layout()],
+        Space::Volume | Space::Sphere => [Boid3d::desc(), mesh_buffer_layout(), Leaders::flag_buffer_layout()],
+    }
+}
+
+// the pipeline drawing the boids of `space` from the text of its render shader
+fn boid_render_pipeline(device: &wgpu::Device, space: Space, camera_bind_group_layout: &wgpu::BindGroupLayout, source: &str) -> wgpu::RenderPipeline {
+    match space {
+        Space::Plane => plane_render_pipeline(device, source),
+        Space::Volume | Space::Sphere => volume_render_pipeline(device, camera_bind_group_layout, source),
+    }
+}
+
+// flat triangles for the 2D mode
+fn plane_render_pipeline(device: &wgpu::Device, source: &str) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("boid_vs_main"), // 1.
+            buffers: &boid_vertex_buffers(Space::Plane), // 2.
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState { // 3.
+            module: &shader,
+            entry_point: Some("boid_fs_main"),
+            targets: &[Some(wgpu::ColorTargetState { // 4.
+                // boids are drawn into the HDR scene texture, the post chain writes `format`
+                format: HDR_FORMAT,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList, // 1.
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw, // 2.
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: None, // 1.
+        multisample: wgpu::MultisampleState {
+            count: 1, // 2.
+            mask: !0, // 3.
+            alpha_to_coverage_enabled: false, // 4.
+        },
+        multiview: None, // 5.
+        cache: None, // 6.
+    })
+}
+
+// instanced cones with normals, depth tested, for the 3D modes
+fn volume_render_pipeline(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout, source: &str) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader 3D"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout 3D"),
+        bind_group_layouts: &[camera_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline 3D"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("boid3d_vs_main"),
+            buffers: &boid_vertex_buffers(Space::Volume),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("boid3d_fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    // a frame the way the window takes one: update, step and draw, submit
+    fn frame(sim: &mut Simulation, target: &OffscreenTarget) {
+        sim.update();
+        let mut encoder = sim.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        sim.encode_frame(&mut encoder, &target.view, target.width, target.height);
+        sim.queue.submit(std::iter::once(encoder.finish()));
+        sim.after_submit();
+        sim.device.poll(wgpu::PollType::Wait).unwrap();
+    }
+
+    // runs on a software adapter, and is skipped where there's none
+    #[test]
+    fn shrinking_the_population_keeps_stepping() {
+        let Ok(mut sim) = pollster::block_on(Simulation::new_headless(true, 1000, Space::Plane)) else {
+            eprintln!("no adapter, skipped");
+            return;
+        };
+        let target = OffscreenTarget::new(&sim.device, 64, 64, sim.format).unwrap();
+        // more boids than the smallest buffers hold, their counts are being read back when the population shrinks.
+        // Paused so the software adapter doesn't have to step them, the counts are read back all the same
+        sim.paused = true;
+        sim.set_population(MAX_POPULATION + 20_000);
+        frame(&mut sim, &target);
+        frame(&mut sim, &target);
+        assert_eq!(sim.num_instances, MAX_POPULATION + 20_000);
+        sim.set_population(10);
+        sim.paused = false;
+        sim.debug.request_pick([0.0, 0.0]);
+        // the pick copies the boids in use, then searches them
+        frame(&mut sim, &target);
+        frame(&mut sim, &target);
+        frame(&mut sim, &target);
+        assert!(sim.num_instances <= 10, "{} boids in use", sim.num_instances);
+        assert_eq!(sim.read_boids().unwrap().len(), sim.num_instances as usize);
+    }
+
+    #[test]
+    fn reloading_the_shaders_keeps_a_snippet_error() {
+        let Ok(mut sim) = pollster::block_on(Simulation::new_headless(true, 100, Space::Plane)) else {
+            eprintln!("no adapter, skipped");
+            return;
+        };
+        assert!(sim.set_extra_force(Some("fn broken( {".to_string())).is_err());
+        // every file counts as changed once watched, so both pipelines are rebuilt, and compile
+        sim.watch_shaders(std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src")));
+        assert_eq!(sim.shader_error, None);
+        assert!(sim.snippet_error.is_some());
+    }
+}
@@ -0,0 +1,18 @@
+use crate::sim::Space;
+
+// User WGSL spliced into the compute shader, to try out a steering rule without changing the crate.
+// A snippet defines
+//     fn extra_force(boid: Boid, neighbor: Boid, d: f32) -> vec2<f32>
+// (vec3<f32> in 3D and on the sphere), which is called for every neighbor the boid sees, `d` apart. The results are summed
+// into its steering like the Reynolds rules, whatever the behavior model. `self` is a reserved word in WGSL,
+// so the first argument can't be called that. The snippet goes in front of the kernel, so the line numbers
+// of its errors are the lines of the snippet itself
+
+// the extra_force of a kernel without a snippet, never called
+pub fn default_extra_force(space: Space) -> String {
+    let ty = match space {
+        Space::Plane => "vec2<f32>",
+        Space::Volume | Space::Sphere => "vec3<f32>",
+    };
+    format!("fn extra_force(boid: Boid, neighbor: Boid, d: f32) -> {ty} {{\n    return {ty}(0.0);\n}}\n")
+}
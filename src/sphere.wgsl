@@ -0,0 +1,58 @@
+// Geometry of the 3D kernel, imported by compute3d.wgsl. It runs the cube of the Volume mode, or the unit sphere
+// of the Sphere mode when SPHERE is set (see sim.rs). On the sphere the velocities are tangent to the surface,
+// neighbors are seen along great circles, so their distance is the geodesic one, and every step ends back on the surface
+
+#import boid3d_types.wgsl
+#import flight.wgsl
+
+// whether the boids fly on the unit sphere, set by models.rs when the pipelines are created
+override SPHERE : bool = false;
+
+// `v` with its part along the surface normal at `pos` taken out, as it is in the cube
+fn tangent(pos: vec3<f32>, v: vec3<f32>) -> vec3<f32> {
+    if (!SPHERE) {
+        return v;
+    }
+    return v - pos * dot(v, pos);
+}
+
+// angle between the directions of `a` and `b`, the geodesic distance of their points on the unit sphere
+fn geodesic_distance(a: vec3<f32>, b: vec3<f32>) -> f32 {
+    return atan2(length(cross(a, b)), dot(a, b));
+}
+
+// Where a boid at `pos` sees a neighbor at `other`. On the sphere that's `pos` plus the tangent vector pointing
+// along the great circle to it, as long as their geodesic distance, so the rules can work with plain vector
+// offsets. Anything off the surface, like the parked dead boids, stays where it is, out of reach
+fn seen_at(pos: vec3<f32>, other: vec3<f32>) -> vec3<f32> {
+    if (!SPHERE || abs(length(other) - 1.0) > 0.5) {
+        return other;
+    }
+    return pos + safe_normalize(tangent(pos, other), vec3<f32>(0.0)) * geodesic_distance(pos, other);
+}
+
+// velocity `vel` of a neighbor carried over to the tangent plane at `pos`, keeping its speed
+fn seen_heading(pos: vec3<f32>, vel: vec3<f32>) -> vec3<f32> {
+    if (!SPHERE) {
+        return vel;
+    }
+    return safe_normalize(tangent(pos, vel), vec3<f32>(0.0)) * length(vel);
+}
+
+// `pos` put back on the sphere, unchanged in the cube
+fn on_surface(pos: vec3<f32>) -> vec3<f32> {
+    if (!SPHERE) {
+        return pos;
+    }
+    return safe_normalize(pos, vec3<f32>(0.0, 1.0, 0.0));
+}
+
+// A boid put back in the world after a step: wrapped around the [-1, 1] cube, or re-projected onto the sphere
+// with its velocity made tangent there at the same speed
+fn settle(boid: Boid) -> Boid {
+    if (!SPHERE) {
+        return Boid(select(boid.pos, -sign(boid.pos), abs(boid.pos) > vec3<f32>(1.0)), boid.vel);
+    }
+    let pos = on_surface(boid.pos);
+    return Boid(pos, seen_heading(pos, boid.vel));
+}
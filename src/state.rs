@@ -1,36 +1,65 @@
 use std::time::{Duration, Instant};
 
 use log::debug;
-use wgpu::{util::DeviceExt, ComputePipelineDescriptor, Instance, PipelineLayoutDescriptor};
-use winit::{event::WindowEvent, window::Window};
-use crate::{boid::{generate_boids, triangle_buffer_layout, Boid, TRIANGLE_VERTICES}, vertex::{Vertex, VERTICES}};
+use winit::{dpi::PhysicalPosition, event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent}, keyboard::{KeyCode, ModifiersState, PhysicalKey}, window::Window};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{capture::timestamped_filename, record::{Recorder, RecordOptions}, scenario::Scenario};
+use crate::{forces::MAX_FORCES, paths::{MAX_PATHS, MAX_PATH_POINTS}, gui::{Gui, PanelStats}, sim::{request_device, Simulation, Space, POPULATION}};
 
-struct Fps {
+// how many boids the spawn button adds
+const SPAWN_BATCH : u32 = 1000;
+// shift + F12 saves a screenshot this many times larger than the window
+const HIGH_RES_SCREENSHOT_SCALE : u32 = 4;
+// 3D camera controls: radians per dragged pixel, zoom per wheel line, and
+// how far one key press flies, relative to the distance from the target
+const ORBIT_SPEED : f32 = 0.005;
+const ZOOM_STEP : f32 = 0.1;
+const FLY_STEP : f32 = 0.05;
+// how close, in world units, a click has to be to a force source's center to grab it
+const FORCE_PICK_DISTANCE : f32 = 0.04;
+// force source radius change per wheel line
+const FORCE_RADIUS_STEP : f32 = 1.1;
+// a drawn path gets a new point every this far, and becomes a loop when it ends this close to its start
+const PATH_POINT_SPACING : f32 = 0.03;
+const PATH_CLOSE_DISTANCE : f32 = 0.08;
+
+pub struct Fps {
     frame_num: usize,
     last_frame_num: usize,
     last_fps_time: Instant,
+    last_frame_time: Instant,
+    pub fps: f32,
+    pub frame_time: Duration,
 }
 
 pub struct State<'a> {
     pub surface: wgpu::Surface<'a>,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
     pub window: &'a Window,
-    pub render_pipeline: wgpu::RenderPipeline,
-    pub compute_pipeline: wgpu::ComputePipeline,
-    pub num_vertices: u32,
-    pub num_instances: u32,
-    pub vertex_buffer: wgpu::Buffer,
-    pub instance_buffers: Vec<wgpu::Buffer>,
-    pub compute_bind_groups: Vec<wgpu::BindGroup>,
+    pub sim: Simulation,
     pub fps: Fps,
+    pub cursor_position: PhysicalPosition<f64>,
+    pub modifiers: ModifiersState,
+    // left mouse button held in the 3D mode, moving the cursor orbits the camera
+    pub orbiting: bool,
+    // left mouse button held on a force source while editing forces, moving the cursor drags it
+    pub dragging_force: bool,
+    // left mouse button held while drawing paths, moving the cursor extends the newest path
+    pub drawing_path: bool,
+    // left mouse button held while drawing the arena, moving the cursor adds corners to its polygon
+    pub drawing_arena: bool,
+    pub gui: Gui,
+    // F9 records every simulation step offscreen until pressed again
+    #[cfg(not(target_arch = "wasm32"))]
+    pub recorder: Option<Recorder>,
 }
 
 impl<'a> State<'a> {
     // Creating some of the wgpu types requires async code
-    pub async fn new(window: &'a Window) -> State<'a> {
+    pub async fn new(window: &'a Window, space: Space) -> State<'a> {
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -47,31 +76,8 @@ impl<'a> State<'a> {
         // the part of window that we draw to.
         let surface = instance.create_surface(window).unwrap();
 
-        // handle for our graphics card.
-        let adapter = instance.request_adapter(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            },
-        ).await.unwrap();
-
-        // create device and queue
-        let (device, queue) = adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
-                // WebGL doesn't support all of wgpu's features, so if
-                // we're building for the web, we'll have to disable some.
-                required_limits: if cfg!(target_arch = "wasm32") {
-                    wgpu::Limits::downlevel_webgl2_defaults()
-                } else {
-                    wgpu::Limits::default()
-                },
-                label: None,
-                memory_hints: Default::default(),
-                trace: wgpu::Trace::Off,
-            },
-        ).await.unwrap();
+        let (adapter, device, queue) = request_device(&instance, Some(&surface), false).await
+            .unwrap_or_else(|err| panic!("couldn't set up the GPU: {}", err));
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -90,182 +96,46 @@ impl<'a> State<'a> {
             desired_maximum_frame_latency: 2,
         };
         
-        const POPULATION : u32 = 50000;
-        let boids = generate_boids(POPULATION);
-        debug!("{:?}", boids);
-        // This is the boid instance buffer, which contains the information of the boids (position & velocity)
-        let num_vertices = TRIANGLE_VERTICES.len() as u32;
-        let num_instances = boids.len() as u32;
-
-        // load in the shaders
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-        });
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[],
-            push_constant_ranges: &[],
-        });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("boid_vs_main"), // 1.
-                buffers: &[
-                    // boid instance buffer layout
-                    Boid::desc(),
-                    //shared triangle buffer layout
-                    triangle_buffer_layout(),
-                ], // 2.
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState { // 3.
-                module: &shader,
-                entry_point: Some("boid_fs_main"),
-                targets: &[Some(wgpu::ColorTargetState { // 4.
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // 1.
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw, // 2.
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None, // 1.
-            multisample: wgpu::MultisampleState {
-                count: 1, // 2.
-                mask: !0, // 3.
-                alpha_to_coverage_enabled: false, // 4.
-            },
-            multiview: None, // 5.
-            cache: None, // 6.
-        });
-
-        // load compute shader
-        let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Compute Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("compute.wgsl").into()),
-        });
-
-        let bind_group_layout  =
-        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new((num_instances * 16) as _), //16 bytes is the size of Boid struct
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: false },
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new((num_instances * 16) as _),
-                    },
-                    count: None,
-                },
-            ],
-            label: None,
-        });
+        let sim = Simulation::new(device, queue, config.format, POPULATION, space);
 
-        let compute_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("compute"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-        let compute_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &compute_shader,
-            entry_point: Some("main"),
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            cache: None,
-        });
-        // shared vertex buffer across all boids.
-        // Since each boid is essentially a triangle, we will redraw this one triangle instance N times,
-        // each with different parameters from the boids array
-        let vertex_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor{
-                label: Some("vertex buffer"),
-                contents: bytemuck::cast_slice(&TRIANGLE_VERTICES),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
-        );
-
-        let mut instance_buffers : Vec<wgpu::Buffer> = Vec::<wgpu::Buffer>::new();
-
-        for i in 0..2 {
-            instance_buffers.push(
-                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Instance Buffer"),
-                    contents: bytemuck::cast_slice(&boids),
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
-                })
-            )
-        };
-        let mut compute_bind_groups : Vec<wgpu::BindGroup> = Vec::<wgpu::BindGroup>::new();
-        for i in 0..2 {
-            compute_bind_groups.push(
-                device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some(&format!("compute bind group {}", i)),
-                    layout: &bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: instance_buffers[i].as_entire_binding(),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: instance_buffers[(i + 1) % 2].as_entire_binding(), // bind to opposite buffer
-                        },
-                    ]
-                })
-            )
-        }
         let fps = Fps {
             frame_num: 0,
             last_frame_num: 0,
             last_fps_time: Instant::now(),
+            last_frame_time: Instant::now(),
+            fps: 0.0,
+            frame_time: Duration::ZERO,
         };
+        let gui = Gui::new(&sim.device, config.format);
 
         Self {
             surface,
-            device,
-            queue,
             config,
             size,
             window,
-            render_pipeline,
-            compute_pipeline,
-            num_vertices,
-            num_instances,
-            vertex_buffer,
-            instance_buffers,
-            compute_bind_groups,
+            sim,
             fps,
+            cursor_position: PhysicalPosition::new(0.0, 0.0),
+            modifiers: ModifiersState::empty(),
+            orbiting: false,
+            dragging_force: false,
+            drawing_path: false,
+            drawing_arena: false,
+            gui,
+            #[cfg(not(target_arch = "wasm32"))]
+            recorder: None,
         }
     }
     pub fn window(&self) -> &Window {
-        &self.window
+        self.window
+    }
+
+    // convert the cursor position in pixels into the [-1, 1] world the boids live in
+    fn cursor_world_position(&self) -> [f32; 2] {
+        [
+            (self.cursor_position.x / self.size.width as f64 * 2.0 - 1.0) as f32,
+            (1.0 - self.cursor_position.y / self.size.height as f64 * 2.0) as f32,
+        ]
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -275,78 +145,385 @@ impl<'a> State<'a> {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            self.surface.configure(&self.sim.device, &self.config);
         }
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
-        false
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                if self.orbiting {
+                    let dx = (position.x - self.cursor_position.x) as f32;
+                    let dy = (position.y - self.cursor_position.y) as f32;
+                    self.sim.camera.orbit(dx * ORBIT_SPEED, dy * ORBIT_SPEED);
+                }
+                let before = self.cursor_world_position();
+                self.cursor_position = *position;
+                if self.dragging_force {
+                    let after = self.cursor_world_position();
+                    self.sim.forces.drag_selected([after[0] - before[0], after[1] - before[1]]);
+                }
+                if self.drawing_path {
+                    self.sim.paths.extend(self.cursor_world_position(), PATH_POINT_SPACING);
+                }
+                if self.drawing_arena {
+                    self.sim.arena.extend(self.cursor_world_position(), PATH_POINT_SPACING);
+                }
+                // leaders in the mouse mode head for the cursor
+                if self.sim.space == Space::Plane {
+                    let [x, y] = self.cursor_world_position();
+                    self.sim.leaders.goal = [x, y, 0.0];
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            _ => {}
+        }
+        // the gui panel gets first pick, so clicking a slider doesn't also select a boid
+        if self.gui.handle_event(event, self.window.scale_factor()) {
+            return true;
+        }
+        if self.sim.space.is_3d() && self.camera_input(event) {
+            return true;
+        }
+        if self.sim.space == Space::Plane && self.sim.arena.drawing && self.arena_input(event) {
+            return true;
+        }
+        if self.sim.space == Space::Plane && self.sim.paths.drawing && self.path_input(event) {
+            return true;
+        }
+        if self.sim.space == Space::Plane && self.sim.forces.editing && self.force_input(event) {
+            return true;
+        }
+        match event {
+            WindowEvent::CursorMoved { .. } => true,
+            // clicking selects the nearest boid for the debug overlay, clicking empty space clears it.
+            // Boids are only picked in 2D, the overlay isn't drawn over the 3D views
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.sim.space == Space::Plane => {
+                self.sim.debug.request_pick(self.cursor_world_position());
+                true
+            }
+            // F12 saves a screenshot at window resolution, shift + F12 at a larger one
+            #[cfg(not(target_arch = "wasm32"))]
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::F12),
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let scale = if self.modifiers.shift_key() { HIGH_RES_SCREENSHOT_SCALE } else { 1 };
+                match self.sim.screenshot(self.size.width * scale, self.size.height * scale) {
+                    Ok(path) => log::info!("Saved screenshot to {}", path.display()),
+                    Err(err) => log::error!("Failed to save screenshot: {}", err),
+                }
+                true
+            }
+            // ctrl + S saves the current setup as a scenario file
+            #[cfg(not(target_arch = "wasm32"))]
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::KeyS),
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if self.modifiers.control_key() => {
+                self.save_scenario();
+                true
+            }
+            // dropping a .ron file on the window loads it as a scenario, a .wgsl file as the extra_force snippet,
+            // an image or grid file as the flow field
+            #[cfg(not(target_arch = "wasm32"))]
+            WindowEvent::DroppedFile(path) => {
+                if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ron")) {
+                    match Scenario::load(path) {
+                        Ok(scenario) => {
+                            scenario.apply(&mut self.sim);
+                            log::info!("Loaded scenario {}", path.display());
+                        }
+                        Err(err) => log::error!("Failed to load scenario {}: {}", path.display(), err),
+                    }
+                    return true;
+                }
+                if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("wgsl")) {
+                    match std::fs::read_to_string(path) {
+                        Ok(source) => match self.sim.set_extra_force(Some(source)) {
+                            Ok(()) => log::info!("Loaded extra_force from {}", path.display()),
+                            Err(err) => log::error!("extra_force in {} doesn't compile:\n{}", path.display(), err),
+                        },
+                        Err(err) => log::error!("Failed to read {}: {}", path.display(), err),
+                    }
+                    return true;
+                }
+                match self.sim.flow.load(&self.sim.queue, path) {
+                    Ok(()) => log::info!("Loaded flow field {}", path.display()),
+                    Err(err) => log::error!("Failed to load flow field {}: {}", path.display(), err),
+                }
+                true
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::F9),
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                self.toggle_recording();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // drag to orbit, scroll to zoom, WASD + QE to fly the camera's target around
+    fn camera_input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                self.orbiting = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 50.0,
+                };
+                self.sim.camera.zoom(lines * ZOOM_STEP);
+                true
+            }
+            // ctrl is left for shortcuts like saving a scenario
+            WindowEvent::KeyboardInput {
+                event: KeyEvent { state: ElementState::Pressed, physical_key: PhysicalKey::Code(code), .. },
+                ..
+            } if !self.modifiers.control_key() => {
+                let (forward, right, up) = match code {
+                    KeyCode::KeyW => (FLY_STEP, 0.0, 0.0),
+                    KeyCode::KeyS => (-FLY_STEP, 0.0, 0.0),
+                    KeyCode::KeyD => (0.0, FLY_STEP, 0.0),
+                    KeyCode::KeyA => (0.0, -FLY_STEP, 0.0),
+                    KeyCode::KeyE => (0.0, 0.0, FLY_STEP),
+                    KeyCode::KeyQ => (0.0, 0.0, -FLY_STEP),
+                    _ => return false,
+                };
+                self.sim.camera.fly(forward, right, up);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // click empty space to place a source, drag one to move it, right click to remove it
+    // and scroll to change the selected one's radius
+    fn force_input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                let position = self.cursor_world_position();
+                let forces = &mut self.sim.forces;
+                match forces.pick(position, FORCE_PICK_DISTANCE) {
+                    Some(index) => forces.selected = Some(index),
+                    None if !forces.place(position) => {
+                        log::warn!("Can't place more than {} force sources", MAX_FORCES);
+                        return true;
+                    }
+                    None => {}
+                }
+                self.dragging_force = true;
+                true
+            }
+            WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+                self.dragging_force = false;
+                true
+            }
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Right, .. } => {
+                if let Some(index) = self.sim.forces.pick(self.cursor_world_position(), FORCE_PICK_DISTANCE) {
+                    self.sim.forces.remove(index);
+                }
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 50.0,
+                };
+                let forces = &mut self.sim.forces;
+                if let Some(source) = forces.selected.and_then(|i| forces.sources.get_mut(i)) {
+                    source.radius = (source.radius * FORCE_RADIUS_STEP.powf(lines)).clamp(0.01, 2.0);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // press to start a path, drag to draw it and release to finish it
+    fn path_input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                if self.sim.paths.begin(self.cursor_world_position()) {
+                    self.drawing_path = true;
+                } else {
+                    log::warn!("Can't draw more than {} paths or {} points", MAX_PATHS, MAX_PATH_POINTS);
+                }
+                true
+            }
+            WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+                if self.drawing_path {
+                    self.sim.paths.end(PATH_CLOSE_DISTANCE);
+                    self.drawing_path = false;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // press to start a polygon arena, drag around the area to fence in and release to close it
+    fn arena_input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                self.sim.arena.begin(self.cursor_world_position());
+                self.drawing_arena = true;
+                true
+            }
+            WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+                if self.drawing_arena {
+                    self.sim.arena.end();
+                    self.drawing_arena = false;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // change the number of boids while running, keeping the ones alive (see `Simulation::set_population`)
+    pub fn set_population(&mut self, count: u32) {
+        self.sim.set_population(count);
+    }
+
+    // write the current parameters, force sources and paths to a scenario file named after the current time
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_scenario(&self) {
+        let path = PathBuf::from(timestamped_filename("scenario", "ron"));
+        match Scenario::from_sim(&self.sim).save(&path) {
+            Ok(()) => log::info!("Saved scenario to {}", path.display()),
+            Err(err) => log::error!("Failed to save scenario: {}", err),
+        }
+    }
+
+    // start recording a PNG sequence at the current window size, or finish the running recording
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn toggle_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(err) = recorder.finish() {
+                log::error!("Failed to finish recording: {}", err);
+            }
+            return;
+        }
+        let output = PathBuf::from(timestamped_filename("recording", "frames"));
+        match Recorder::new(&self.sim, RecordOptions::new(output, self.size.width, self.size.height)) {
+            Ok(recorder) => {
+                log::info!("Recording to {}", recorder.options.output.display());
+                self.recorder = Some(recorder);
+            }
+            Err(err) => log::error!("Failed to start recording: {}", err),
+        }
     }
 
     pub fn update(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.sim.reload_shaders();
+        self.sim.update();
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         let output = self.surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor{
+        let mut encoder = self.sim.device.create_command_encoder(&wgpu::CommandEncoderDescriptor{
             label: Some("RenderEncoder"),
         });
-        // compute pass
-        {  
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Compute Pass"), 
-                timestamp_writes: None 
-            });
-            compute_pass.set_pipeline(&self.compute_pipeline);
-            compute_pass.set_bind_group(0,&self.compute_bind_groups[self.fps.frame_num % 2], &[]);
-            compute_pass.dispatch_workgroups((self.num_instances + 63) / 64, 1, 1);
-        }
-        // render pass
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment { 
-                    view: &view, 
-                    resolve_target: None, 
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color { 
-                            r: 0.3, 
-                            g: 0.0, 
-                            b: 0.075, 
-                            a: 1.0 
-                        }),
-                        store: wgpu::StoreOp::Store,
-
-                    },
-                })],
-                depth_stencil_attachment:None,
-                occlusion_query_set:None,
-                timestamp_writes: None,
-            });
-            
-            render_pass.set_pipeline(&self.render_pipeline);
-            
-            render_pass.set_vertex_buffer(0, self.instance_buffers[(self.fps.frame_num + 1) % 2].slice(..));        // N boids
-            render_pass.set_vertex_buffer(1, self.vertex_buffer.slice(..));
-
-            render_pass.draw(0..3, 0..self.num_instances as u32); // 3 vertices, N instances
-        }
+        // compute and render pass
+        let step = self.sim.step;
+        self.sim.encode_frame(&mut encoder, &view, self.config.width, self.config.height);
         self.fps.frame_num+=1;
 
         let now = Instant::now();
+        self.fps.frame_time = now.duration_since(self.fps.last_frame_time);
+        self.fps.last_frame_time = now;
         let elapsed = now.duration_since(self.fps.last_fps_time);
 
         if elapsed >= Duration::from_secs(1) {
             let frame_count = self.fps.frame_num - self.fps.last_frame_num;
-            let fps = frame_count as f32 / elapsed.as_secs_f32();
-            println!("FPS: {:.2}", fps);
+            self.fps.fps = frame_count as f32 / elapsed.as_secs_f32();
+            debug!("FPS: {:.2}", self.fps.fps);
 
             self.fps.last_fps_time = now;
+            self.fps.last_frame_num = self.fps.frame_num;
         }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+        // gui pass, drawn last so the panel sits on top of the boids and the overlay
+        let stats = PanelStats {
+            fps: self.fps.fps,
+            frame_time_ms: self.fps.frame_time.as_secs_f32() * 1000.0,
+            population: self.sim.lifecycle.live,
+            slots: self.sim.num_instances,
+            capacity: self.sim.max_instances,
+            #[cfg(not(target_arch = "wasm32"))]
+            recorded_frames: self.recorder.as_ref().map(|recorder| recorder.frames_written),
+            #[cfg(target_arch = "wasm32")]
+            recorded_frames: None,
+        };
+        let actions = self.gui.draw(
+            &mut self.sim,
+            &mut encoder,
+            &view,
+            [self.config.width, self.config.height],
+            self.window.scale_factor(),
+            &stats,
+        );
+
+        self.sim.queue.submit(std::iter::once(encoder.finish()));
+        self.sim.after_submit();
         output.present();
+
+        // only steps are recorded, so pausing doesn't add duplicate frames
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.sim.step != step
+            && let Some(recorder) = &mut self.recorder
+            && let Err(err) = recorder.capture(&mut self.sim)
+        {
+            log::error!("Failed to record frame, stopping: {}", err);
+            self.recorder = None;
+        }
+
+        if actions.reset {
+            self.sim.reset();
+        }
+        if actions.spawn {
+            self.sim.spawn(SPAWN_BATCH);
+        }
+        if let Some(count) = actions.set_population {
+            self.set_population(count);
+        }
+        if actions.clear_extra_force && let Err(err) = self.sim.set_extra_force(None) {
+            log::error!("Failed to rebuild the kernels: {}", err);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if actions.save_scenario {
+            self.save_scenario();
+        }
         Ok(())
     }
 }